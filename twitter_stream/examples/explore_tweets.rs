@@ -20,6 +20,9 @@ fn main() -> Result<()> {
         }
     }
 
+    // ids are snowflakes, sorting them sorts the tweets by creation time
+    data.sort_by_key(|o| o.data.id);
+
     let n = 5;
     let bold = Style::new().bold();
     let msg = format!("{} tweets found", data.len());
@@ -27,8 +30,9 @@ fn main() -> Result<()> {
     let blue = bold.blue();
 
     data.iter().rev().take(n).for_each(|o| {
-        let url = tweetid2url(&o.data.id);
-        println!("- {}: \"{}\"", blue.apply_to(url), o.data.text);
+        let url = tweetid2url(o.data.id);
+        let time = o.data.id.timestamp().format("%Y-%m-%d %H:%M:%S");
+        println!("- [{}] {}: \"{}\"", time, blue.apply_to(url), o.data.text);
     });
 
     Ok(())
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use clap::{AppSettings, Clap};
 use console::Style;
 use csv::Writer;
@@ -7,7 +8,10 @@ use std::{
     collections::{HashMap, HashSet},
     path::Path,
 };
-use twitter_stream::StreamResponse;
+use twitter_stream::{
+    edges::{read_edges, EdgeClass, NodeClass},
+    StreamResponse, TweetId,
+};
 
 /// Producs node and edges files with graph information from a JSON Lines file
 /// with `StreamResponse` items
@@ -22,6 +26,10 @@ struct Opts {
     /// Edges output name
     #[clap(short, long)]
     edges_file: Option<String>,
+    /// Edge list to merge into the graph, as written by the `timelines` and
+    /// `follows` subcommands (can be repeated)
+    #[clap(long = "extra-edges", number_of_values = 1)]
+    extra_edges: Vec<String>,
     #[clap(short, long, parse(from_occurrences))]
     verbose: i32,
 }
@@ -31,7 +39,7 @@ fn read_file<P: AsRef<Path>>(file: P, verbose: bool) -> Result<(Vec<StreamRespon
     let mut errors = 0;
     let items = txt
         .split('\n')
-        .map(|line| serde_json::from_str::<StreamResponse>(line))
+        .map(serde_json::from_str::<StreamResponse>)
         .filter_map(|o| match o {
             Ok(res) => Some(res),
             Err(err) => {
@@ -54,15 +62,11 @@ fn read_file<P: AsRef<Path>>(file: P, verbose: bool) -> Result<(Vec<StreamRespon
 #[derive(Serialize)]
 struct NodeRow<'a> {
     id: usize,
-    label: &'a str,
+    label: String,
     class: NodeClass,
     text: Option<&'a str>,
-}
-
-#[derive(Serialize)]
-enum NodeClass {
-    User,
-    Tweet,
+    /// Creation time of the tweet (decoded from its id)
+    timestamp: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
@@ -82,13 +86,6 @@ enum EdgeType {
     Undirected,
 }
 
-#[derive(Serialize)]
-enum EdgeClass {
-    TweetOwner,
-    ReferencedTweet,
-    UserMention,
-}
-
 fn main() -> Result<()> {
     let opts = Opts::parse();
     let bold = Style::new().bold();
@@ -114,7 +111,9 @@ fn main() -> Result<()> {
     edges_path.push(edges_fname);
 
     println!("Obtaining graph from {}", bold.apply_to(&opts.jsonl_file));
-    let (data, errors) = read_file(source_file, verbose)?;
+    let (mut data, errors) = read_file(source_file, verbose)?;
+    // ids are snowflakes, sorting them sorts the tweets by creation time
+    data.sort_by_key(|o| o.data.id);
     println!("Found {} items", green.apply_to(data.len()));
     if errors > 0 {
         println!("Found {} errors", red.apply_to(errors));
@@ -122,19 +121,20 @@ fn main() -> Result<()> {
 
     // Nodes
     let mut writer = Writer::from_path(&nodes_path)?;
-    // 1. get user nodes
+    // 1. get user nodes (authors and the mentioned users added by `hydrate`)
     let user_nodes = data
         .iter()
-        .filter_map(|o| o.includes.users.get(0))
+        .flat_map(|o| &o.includes.users)
         .map(|o| &o.username)
         .collect::<HashSet<_>>()
         .into_iter()
         .enumerate()
         .map(|(i, label)| NodeRow {
             id: i,
-            label,
+            label: label.clone(),
             class: NodeClass::User,
             text: None,
+            timestamp: None,
         })
         .collect::<Vec<_>>();
 
@@ -144,14 +144,56 @@ fn main() -> Result<()> {
         .enumerate()
         .map(|(i, tweet)| NodeRow {
             id: i + user_nodes.len(),
-            label: &tweet.data.id,
+            label: tweet.data.id.to_string(),
             class: NodeClass::Tweet,
             text: Some(&tweet.data.text),
+            timestamp: Some(tweet.data.id.timestamp()),
         })
         .collect::<Vec<_>>();
 
-    // 3. write to nodes file
-    user_nodes.iter().chain(tweet_nodes.iter()).for_each(|row| {
+    // 3. get the nodes of the extra edges that are not in the data
+    let mut extra_edges = Vec::new();
+    let mut seen = HashSet::new();
+    for path in &opts.extra_edges {
+        for edge in read_edges(path)? {
+            if seen.insert(edge.clone()) {
+                extra_edges.push(edge);
+            }
+        }
+    }
+    let mut labels = user_nodes
+        .iter()
+        .chain(tweet_nodes.iter())
+        .map(|row| row.label.clone())
+        .collect::<HashSet<_>>();
+    let mut extra_nodes = Vec::new();
+    for edge in &extra_edges {
+        let ends = vec![
+            (&edge.source, edge.source_class),
+            (&edge.target, edge.target_class),
+        ];
+        for (label, class) in ends {
+            if labels.insert(label.clone()) {
+                extra_nodes.push(NodeRow {
+                    id: user_nodes.len() + tweet_nodes.len() + extra_nodes.len(),
+                    label: label.clone(),
+                    class,
+                    text: None,
+                    timestamp: match class {
+                        NodeClass::Tweet => label.parse::<TweetId>().ok().map(|o| o.timestamp()),
+                        NodeClass::User => None,
+                    },
+                });
+            }
+        }
+    }
+
+    // 4. write to nodes file
+    let nodes = user_nodes
+        .iter()
+        .chain(tweet_nodes.iter())
+        .chain(extra_nodes.iter());
+    nodes.for_each(|row| {
         let res = writer.serialize(row);
         if res.is_err() && verbose {
             println!("{:?}", res);
@@ -171,16 +213,17 @@ fn main() -> Result<()> {
     let nodes_map = user_nodes
         .iter()
         .chain(tweet_nodes.iter())
-        .map(|row| (row.label, row.id))
+        .chain(extra_nodes.iter())
+        .map(|row| (row.label.as_str(), row.id))
         .collect::<HashMap<_, _>>();
 
     // 2. username -> tweet (tweet owner)
     data.iter()
         .filter_map(|tweet| {
-            if let Some(user) = tweet.includes.users.get(0) {
+            if let Some(user) = tweet.includes.users.first() {
                 if let (Some(&source), Some(&target)) = (
                     nodes_map.get(user.username.as_str()),
-                    nodes_map.get(tweet.data.id.as_str()),
+                    nodes_map.get(tweet.data.id.to_string().as_str()),
                 ) {
                     let edge = EdgeRow {
                         source,
@@ -207,11 +250,11 @@ fn main() -> Result<()> {
         .filter_map(|tweet| {
             if let (Some(references), Some(&source)) = (
                 &tweet.data.referenced_tweets,
-                nodes_map.get(tweet.data.id.as_str()),
+                nodes_map.get(tweet.data.id.to_string().as_str()),
             ) {
                 let edge_info = references
                     .iter()
-                    .filter_map(|reference| nodes_map.get(reference.id.as_str()))
+                    .filter_map(|reference| nodes_map.get(reference.id.to_string().as_str()))
                     .map(move |&target| (source, target));
                 return Some(edge_info);
             }
@@ -237,9 +280,10 @@ fn main() -> Result<()> {
     // 4. tweet -> user mentions
     data.iter()
         .filter_map(|tweet| {
-            if let (Some(&source), Some(entities)) =
-                (nodes_map.get(tweet.data.id.as_str()), &tweet.data.entities)
-            {
+            if let (Some(&source), Some(entities)) = (
+                nodes_map.get(tweet.data.id.to_string().as_str()),
+                &tweet.data.entities,
+            ) {
                 if let Some(mentions) = &entities.mentions {
                     let edge_info = mentions
                         .iter()
@@ -267,6 +311,34 @@ fn main() -> Result<()> {
             }
         });
 
+    // 5. extra edges
+    extra_edges
+        .iter()
+        .filter_map(|edge| {
+            match (
+                nodes_map.get(edge.source.as_str()),
+                nodes_map.get(edge.target.as_str()),
+            ) {
+                (Some(&source), Some(&target)) => Some((source, target, edge.class)),
+                _ => None,
+            }
+        })
+        .for_each(|(source, target, class)| {
+            let row = EdgeRow {
+                source,
+                target,
+                typ: EdgeType::Directed,
+                id: i,
+                class,
+            };
+            let res = writer.serialize(row);
+            if res.is_err() && verbose {
+                println!("{:?}", res);
+            } else {
+                i += 1;
+            }
+        });
+
     writer.flush()?;
     println!(
         "Edges saved on: {}",
@@ -60,7 +60,7 @@ impl Summary {
             let results = items
                 .iter()
                 .filter_map(|o| match &o["index"] {
-                    Value::Object(index) => index.get("result").map(|o| o.as_str()).flatten(),
+                    Value::Object(index) => index.get("result").and_then(|o| o.as_str()),
                     _ => None,
                 })
                 .collect::<Vec<_>>();
@@ -125,7 +125,7 @@ async fn send_message_batch(
 ) -> Result<Value> {
     let body = batch
         .iter()
-        .map(|o| BulkOperation::index(o).id(&o.data.id).into())
+        .map(|o| BulkOperation::index(o).id(o.data.id.to_string()).into())
         .collect::<Vec<BulkOperation<_>>>();
     let response = client
         .bulk(BulkParts::Index(index))
@@ -57,8 +57,9 @@ async fn send_message(
     client: &Elasticsearch,
     index: &str,
 ) -> Result<ESResponse> {
+    let id = msg.data.id.to_string();
     let response = client
-        .index(IndexParts::IndexId(index, &msg.data.id))
+        .index(IndexParts::IndexId(index, &id))
         .body(&msg)
         .send()
         .await?;
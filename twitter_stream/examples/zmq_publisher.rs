@@ -2,7 +2,11 @@ use anyhow::Result;
 use clap::{AppSettings, Clap};
 use console::{Style, Term};
 use futures::StreamExt;
-use twitter_stream::{get_bearer_token, stream_data, StreamError};
+use std::time::Duration;
+use twitter_stream::{
+    auth::DEFAULT_TOKEN_CACHE, ApiConfig, ReconnectOptions, ResilientStream, StreamError,
+    StreamEvent, TokenCache, TwitterClient,
+};
 
 /// ZeroMQ publisher of Twitter stream
 #[derive(Clap, Debug)]
@@ -12,15 +16,27 @@ struct Opts {
     #[clap(short, long)]
     limit: Option<usize>,
     /// Token for twitter authentification, if not given the program
-    /// will look for the environment variable BEARER_TOKEN.
+    /// will look for the environment variable BEARER_TOKEN, or request
+    /// one with API_KEY and API_SECRET.
     #[clap(short, long)]
     bearer_token: Option<String>,
-    /// Enviroment file to look for $BEARER_TOKEN.
+    /// Enviroment file to look for $BEARER_TOKEN (or $API_KEY and $API_SECRET).
     #[clap(long, default_value = ".env")]
     env_file: String,
     /// Maximum number of connection resets while streaming
     #[clap(short, long)]
     max_resets: Option<usize>,
+    /// Recover the tweets sent while the stream was disconnected (up to 5 minutes),
+    /// requires Academic Research access
+    #[clap(long)]
+    backfill: bool,
+    /// Base url of the Twitter API, change it to use a proxy or a local mock server
+    #[clap(long, default_value = "https://api.twitter.com")]
+    api_base_url: String,
+    /// Seconds without receiving data (tweets or keep-alive signals)
+    /// before resetting the stream connection
+    #[clap(long, default_value = "30")]
+    stall_timeout: u64,
     #[clap(short, long, parse(from_occurrences))]
     verbose: i32,
     /// IP to bind the ZeroMQ socket
@@ -80,33 +96,42 @@ async fn main() -> Result<()> {
     let term = Term::stdout();
     let bold = Style::new().bold();
 
-    let bearer_token =
-        get_bearer_token(opts.bearer_token.as_deref(), Some(opts.env_file.as_str()))?;
+    let client = TwitterClient::authenticate(
+        opts.bearer_token.as_deref(),
+        Some(opts.env_file.as_str()),
+        Some(&TokenCache::new(DEFAULT_TOKEN_CACHE)),
+        ApiConfig {
+            stall_timeout: Some(Duration::from_secs(opts.stall_timeout)),
+            ..ApiConfig::new(&opts.api_base_url)
+        },
+    )
+    .await?;
 
     let ctx = zmq::Context::new();
     let socket_type = if opts.socket_pub { zmq::PUB } else { zmq::PUSH };
     let publisher = ctx.socket(socket_type)?;
     publisher.bind(&format!("tcp://{}:{}", opts.bind_ip, opts.bind_port))?;
 
-    let mut connection_resets = 0;
     let mut finish = false;
 
-    let (mut rate_limit, mut stream) = stream_data(&bearer_token).await?;
-    if opts.verbose > 0 {
-        println!("{:?}", rate_limit);
-    }
+    let mut stream = ResilientStream::new(
+        client,
+        ReconnectOptions {
+            max_resets: opts.max_resets,
+            backfill: opts.backfill,
+            ..Default::default()
+        },
+    );
     let mut summary = Summary::new(opts.limit);
     println!("{}", bold.apply_to("Starting the stream..."));
     summary.show();
 
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            Ok(tweet_data) => {
+    while let Some(event) = stream.next().await {
+        match event {
+            Ok(StreamEvent::Tweet(tweet_data)) => {
                 if let Ok(msg) = serde_json::to_string(&tweet_data) {
                     if opts.socket_pub {
-                        publisher
-                            .send_multipart(&[&opts.envelope_key, &msg], 0)
-                            .ok();
+                        publisher.send_multipart([&opts.envelope_key, &msg], 0).ok();
                     } else {
                         // push socket doesn't allow envelope filter
                         publisher.send(&msg, 0).ok();
@@ -127,42 +152,35 @@ async fn main() -> Result<()> {
                     }
                 }
             }
-            Err(StreamError::SmallChunk) => {}
-            Err(StreamError::Parse(err)) => {
+            Ok(StreamEvent::Error(StreamError::Parse(err))) => {
                 eprintln!(
                     "Couldn't parse tweet data:\n{}\n{:?}\n\n",
                     err.source, err.msg
                 );
                 summary.errors += 1;
             }
-            Err(StreamError::Reqwest(err)) => {
-                // Try to reconnect
+            Ok(StreamEvent::Error(err)) => {
                 if opts.verbose > 0 {
-                    eprintln!("Error reading chunk of data: {:#?}", err);
+                    eprintln!("Error reading the stream: {:#?}", err);
                 }
                 summary.errors += 1;
-
-                if let Some(max_resets) = opts.max_resets {
-                    if connection_resets >= max_resets {
-                        println!(
-                            "Maximum number of connection resets ({}) reached...",
-                            max_resets
-                        );
-                        break;
-                    }
+            }
+            Ok(StreamEvent::Connected { rate_limit, .. }) => {
+                if opts.verbose > 0 {
+                    println!("{:?}", rate_limit);
                 }
-
-                if let Some(rest) = rate_limit.duration_until_reset() {
-                    println!("Waiting for rate limit ({:?})...", rest);
-                    tokio::time::sleep(rest).await;
-                    println!("Resetting connection...\n\n");
+            }
+            Ok(StreamEvent::Reconnecting { wait, reason, .. }) => {
+                if opts.verbose > 0 {
+                    eprintln!("Connection lost: {}", reason);
                 }
-
-                let (rl, s) = stream_data(&bearer_token).await?;
-
-                connection_resets += 1;
-                rate_limit = rl;
-                stream = s;
+                summary.errors += 1;
+                println!("Resetting connection in {:?}...", wait);
+                summary.show();
+            }
+            Err(err) => {
+                println!("{}...", err);
+                break;
             }
         }
     }
@@ -19,7 +19,7 @@ fn main() -> Result<()> {
         let _envelop = subscriber.recv_msg(0)?;
         let msg = subscriber.recv_bytes(0)?; //.unwrap();
         let tweet = serde_json::from_slice::<StreamResponse>(&msg)?;
-        let url = tweetid2url(&tweet.data.id);
+        let url = tweetid2url(tweet.data.id);
         println!("- {}: \"{}\"", blue.apply_to(url), tweet.data.text);
     }
 }
@@ -0,0 +1,176 @@
+use crate::stream_tweets::RateLimitHeaders;
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Time to wait after a 429 response without rate limit headers
+pub const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Errors reported by the Twitter API, either as an HTTP error or as a message
+/// sent through the stream. See:
+/// https://developer.twitter.com/en/support/twitter-api/error-troubleshooting
+#[derive(Error, Debug)]
+pub enum TwitterApiError {
+    /// The stream couldn't be connected (eg: too many connections)
+    #[error("Connection exception: {0}")]
+    ConnectionException(Problem),
+    /// Twitter closed the stream (eg: for maintenance), it's safe to reconnect
+    #[error("Operational disconnect: {0}")]
+    OperationalDisconnect(Problem),
+    /// The monthly tweet cap of the project was reached
+    #[error("Usage cap exceeded: {0}")]
+    UsageCapExceeded(Problem),
+    #[error("Rate limited: {0}")]
+    RateLimited(Problem, RateLimitHeaders),
+    /// Invalid credentials or not enough access level for the resource
+    #[error("Unauthorized: {0}")]
+    Unauthorized(Problem),
+    #[error("{0}")]
+    Other(Problem),
+}
+
+/// Problem object returned by the API v2
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Problem {
+    /// The v1.1 endpoints (eg: oauth2) send a `message` instead
+    #[serde(default, alias = "message")]
+    pub title: String,
+    pub detail: Option<String>,
+    #[serde(rename = "type")]
+    pub problem_type: Option<String>,
+    pub status: Option<u16>,
+    pub disconnect_type: Option<String>,
+    pub connection_issue: Option<String>,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title)?;
+        match &self.detail {
+            Some(detail) if detail != &self.title => write!(f, " ({})", detail),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Problem {
+    /// Without title, type or detail
+    fn is_empty(&self) -> bool {
+        self.title.is_empty() && self.problem_type.is_none() && self.detail.is_none()
+    }
+
+    /// Checks the title or the last part of the type url
+    /// (eg: "https://api.twitter.com/2/problems/usage-capped")
+    fn is(&self, name: &str) -> bool {
+        self.title.eq_ignore_ascii_case(name)
+            || self
+                .problem_type
+                .as_deref()
+                .and_then(|o| o.rsplit('/').next())
+                .map(|o| o == name)
+                .unwrap_or(false)
+    }
+}
+
+/// Body of an error response, the API uses both a list of problems
+/// and a single problem object
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ErrorResponse {
+    Errors { errors: Vec<Problem> },
+    Single(Problem),
+}
+
+impl ErrorResponse {
+    /// `None` without problems, as any JSON object reads as an empty `Single`
+    /// problem (eg: a tweet that couldn't be parsed)
+    pub fn into_problem(self) -> Option<Problem> {
+        match self {
+            ErrorResponse::Errors { errors } => errors.into_iter().next(),
+            ErrorResponse::Single(problem) => Some(problem),
+        }
+        .filter(|o| !o.is_empty())
+    }
+}
+
+impl TwitterApiError {
+    pub fn from_problem(problem: Problem) -> Self {
+        if problem.is("operational-disconnect") {
+            TwitterApiError::OperationalDisconnect(problem)
+        } else if problem.is("ConnectionException") || problem.is("streaming-connection") {
+            TwitterApiError::ConnectionException(problem)
+        } else if problem.is("UsageCapExceeded") || problem.is("usage-capped") {
+            TwitterApiError::UsageCapExceeded(problem)
+        } else if problem.is("Unauthorized")
+            || problem.is("not-authorized-for-resource")
+            || problem.is("unsupported-authentication")
+            || problem.is("client-forbidden")
+        {
+            TwitterApiError::Unauthorized(problem)
+        } else {
+            TwitterApiError::Other(problem)
+        }
+    }
+
+    /// Builds the error from a failed HTTP response
+    pub fn from_status(status: StatusCode, rate_limit: RateLimitHeaders, body: &str) -> Self {
+        let problem = serde_json::from_str::<ErrorResponse>(body)
+            .ok()
+            .and_then(|o| o.into_problem())
+            .unwrap_or_else(|| Problem {
+                title: status.canonical_reason().unwrap_or("Unknown").into(),
+                detail: Some(body.to_string()).filter(|o| !o.is_empty()),
+                ..Default::default()
+            });
+
+        match Self::from_problem(problem) {
+            TwitterApiError::Other(problem) | TwitterApiError::ConnectionException(problem)
+                if status == StatusCode::TOO_MANY_REQUESTS =>
+            {
+                TwitterApiError::RateLimited(problem, rate_limit)
+            }
+            TwitterApiError::Other(problem)
+                if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN =>
+            {
+                TwitterApiError::Unauthorized(problem)
+            }
+            err => err,
+        }
+    }
+
+    /// Time to wait before retrying the request, `None` if it can't be retried
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TwitterApiError::RateLimited(_, rate_limit) => Some(
+                rate_limit
+                    .duration_until_reset()
+                    .unwrap_or(DEFAULT_RATE_LIMIT_WAIT),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn problem(&self) -> &Problem {
+        match self {
+            TwitterApiError::ConnectionException(problem)
+            | TwitterApiError::OperationalDisconnect(problem)
+            | TwitterApiError::UsageCapExceeded(problem)
+            | TwitterApiError::RateLimited(problem, _)
+            | TwitterApiError::Unauthorized(problem)
+            | TwitterApiError::Other(problem) => problem,
+        }
+    }
+}
+
+/// Returns the response if it was successful, otherwise reads the body to
+/// build a `TwitterApiError`
+pub async fn check_response(res: Response) -> anyhow::Result<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let rate_limit = RateLimitHeaders::from_headers(res.headers())?;
+    let body = res.text().await.unwrap_or_default();
+    Err(TwitterApiError::from_status(status, rate_limit, &body).into())
+}
@@ -0,0 +1,245 @@
+//! App-only authentication (OAuth 2.0 client credentials), the bearer token is
+//! obtained from the API key and secret of the app. See:
+//! https://developer.twitter.com/en/docs/authentication/oauth-2-0/bearer-tokens
+use crate::{
+    api_error::check_response, get_bearer_token, ApiConfig, CredentialPool, TwitterClient,
+};
+use anyhow::{Context, Result};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+pub const OAUTH2_TOKEN_PATH: &str = "/oauth2/token";
+pub const OAUTH2_INVALIDATE_PATH: &str = "/oauth2/invalidate_token";
+
+/// File used by the CLI to keep the token obtained from the credentials
+pub const DEFAULT_TOKEN_CACHE: &str = ".bearer_token.json";
+
+/// API key and secret of the app (the "consumer keys" on the developer portal)
+#[derive(Clone)]
+pub struct AppCredentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+// Keeps the secret out of the logs
+impl std::fmt::Debug for AppCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AppCredentials")
+            .field("api_key", &self.api_key)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token_type: String,
+    access_token: String,
+}
+
+impl AppCredentials {
+    pub fn new<T: Into<String>, U: Into<String>>(api_key: T, api_secret: U) -> Self {
+        Self {
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+        }
+    }
+
+    /// Reads `$API_KEY` and `$API_SECRET`, `None` if any of them is not set
+    pub fn from_env(env_file: Option<&str>) -> Option<Self> {
+        if let Some(env_file) = env_file {
+            dotenv::from_filename(env_file).ok();
+        }
+        let api_key = std::env::var("API_KEY").ok()?;
+        let api_secret = std::env::var("API_SECRET").ok()?;
+        Some(Self::new(api_key, api_secret))
+    }
+
+    /// Requests a new bearer token (without the "Bearer " prefix), twitter
+    /// returns the same token until it is invalidated
+    pub async fn request_token(&self, config: &ApiConfig) -> Result<String> {
+        let res = self
+            .post(OAUTH2_TOKEN_PATH, config)?
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?;
+        let res = check_response(res)
+            .await
+            .context("Couldn't obtain a bearer token")?
+            .text()
+            .await?;
+        let res = serde_json::from_str::<TokenResponse>(&res)
+            .with_context(|| format!("Couldn't parse response:\n{}", res))?;
+        if !res.token_type.eq_ignore_ascii_case("bearer") {
+            anyhow::bail!("Unexpected token type: {:?}", res.token_type);
+        }
+        Ok(res.access_token)
+    }
+
+    /// Invalidates a token obtained with these credentials, it can't be used
+    /// anymore and the next `request_token` will return a new one
+    pub async fn invalidate_token(&self, token: &str, config: &ApiConfig) -> Result<()> {
+        let res = self
+            .post(OAUTH2_INVALIDATE_PATH, config)?
+            .form(&[("access_token", token)])
+            .send()
+            .await?;
+        check_response(res)
+            .await
+            .context("Couldn't invalidate the bearer token")?;
+        Ok(())
+    }
+
+    /// Token from the cache, or a new one (saved on the cache)
+    pub async fn token(&self, cache: Option<&TokenCache>, config: &ApiConfig) -> Result<String> {
+        if let Some(token) = cache.map(|o| o.load(self)).transpose()?.flatten() {
+            return Ok(token);
+        }
+        let token = self.request_token(config).await?;
+        if let Some(cache) = cache {
+            cache.save(self, &token)?;
+        }
+        Ok(token)
+    }
+
+    fn post(&self, path: &str, config: &ApiConfig) -> Result<RequestBuilder> {
+        let request = config
+            .build_client()?
+            .post(config.url(path))
+            .basic_auth(&self.api_key, Some(&self.api_secret));
+        Ok(match config.timeout {
+            Some(duration) => request.timeout(duration),
+            None => request,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedToken {
+    api_key: String,
+    access_token: String,
+}
+
+/// JSON file with the last token obtained from the credentials, the token is
+/// ignored if it was obtained with another API key
+#[derive(Clone, Debug)]
+pub struct TokenCache {
+    path: PathBuf,
+}
+
+impl TokenCache {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self, credentials: &AppCredentials) -> Result<Option<String>> {
+        let txt = match std::fs::read_to_string(&self.path) {
+            Ok(txt) => txt,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("Couldn't read {:?}", self.path)),
+        };
+        let cached = serde_json::from_str::<CachedToken>(&txt)
+            .with_context(|| format!("Couldn't parse the token cache {:?}", self.path))?;
+        if cached.api_key != credentials.api_key {
+            return Ok(None);
+        }
+        Ok(Some(cached.access_token))
+    }
+
+    pub fn save(&self, credentials: &AppCredentials, token: &str) -> Result<()> {
+        let cached = CachedToken {
+            api_key: credentials.api_key.clone(),
+            access_token: token.to_string(),
+        };
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // the token gives access to the API, only the owner should read it
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options
+            .open(&self.path)
+            .with_context(|| format!("Couldn't create {:?}", self.path))?;
+        serde_json::to_writer(file, &cached)?;
+        Ok(())
+    }
+
+    pub fn remove(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Couldn't remove {:?}", self.path))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tokens for the authorization header, from (in order): `bearer_token`,
+/// `$BEARER_TOKEN_1..N`, `$BEARER_TOKEN` or a token obtained with `$API_KEY`
+/// and `$API_SECRET` (kept on `cache`). The numbered tokens go first so a
+/// pool can be added to an environment that already has `$BEARER_TOKEN`
+pub async fn authenticate(
+    bearer_token: Option<&str>,
+    env_file: Option<&str>,
+    cache: Option<&TokenCache>,
+    config: &ApiConfig,
+) -> Result<CredentialPool> {
+    if let Some(env_file) = env_file {
+        dotenv::from_filename(env_file).ok();
+    }
+    if bearer_token.is_none() {
+        if let Some(pool) = CredentialPool::from_env(None) {
+            return Ok(pool);
+        }
+    }
+    if bearer_token.is_some() || std::env::var("BEARER_TOKEN").is_ok() {
+        return CredentialPool::new(vec![get_bearer_token(bearer_token, None)?]);
+    }
+    let credentials = AppCredentials::from_env(None).context(
+        "No credentials found, set $BEARER_TOKEN_1..N, $BEARER_TOKEN (or specify it with --bearer-token), or $API_KEY and $API_SECRET",
+    )?;
+    let token = credentials.token(cache, config).await?;
+    CredentialPool::new(vec![format!("Bearer {}", token)])
+}
+
+impl TwitterClient {
+    /// Creates the client with the token obtained from `authenticate`
+    pub async fn authenticate(
+        bearer_token: Option<&str>,
+        env_file: Option<&str>,
+        cache: Option<&TokenCache>,
+        config: ApiConfig,
+    ) -> Result<Self> {
+        let credentials = authenticate(bearer_token, env_file, cache, &config).await?;
+        Self::with_credentials(credentials, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only test that changes the environment
+    #[tokio::test]
+    async fn numbered_tokens_go_first() {
+        std::env::set_var("BEARER_TOKEN", "single");
+        let config = ApiConfig::default();
+        let pool = authenticate(None, None, None, &config).await.unwrap();
+        assert_eq!(pool.len(), 1);
+
+        std::env::set_var("BEARER_TOKEN_1", "first");
+        std::env::set_var("BEARER_TOKEN_2", "second");
+        let pool = authenticate(None, None, None, &config).await.unwrap();
+        assert_eq!(pool.len(), 2);
+        let pool = authenticate(Some("given"), None, None, &config)
+            .await
+            .unwrap();
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    api_error::check_response,
+    credentials::{endpoint, Acquire, CredentialPool},
+    fields::FieldSelection,
+    get_bearer_token,
+    stream_tweets::RateLimitHeaders,
+    ApiConfig, TwitterApiError,
+};
+use anyhow::{Context, Result};
+use reqwest::{header, Client, Method, RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Client for the Twitter API v2, it keeps one HTTP client (and its connection pool)
+/// for all the calls.
+///
+/// The endpoints are implemented on the modules that use them (eg: `TwitterClient::stream`
+/// lives in `stream_tweets`).
+#[derive(Clone, Debug)]
+pub struct TwitterClient {
+    http: Client,
+    credentials: CredentialPool,
+    config: ApiConfig,
+    fields: FieldSelection,
+}
+
+impl TwitterClient {
+    /// `bearer_token` is the value for the authorization header (eg: "Bearer XXXX"),
+    /// as returned by `get_bearer_token`
+    pub fn new<T: Into<String>>(bearer_token: T, config: ApiConfig) -> Result<Self> {
+        Self::with_credentials(CredentialPool::new(vec![bearer_token.into()])?, config)
+    }
+
+    /// Creates the client rotating through the tokens of `credentials`
+    pub fn with_credentials(credentials: CredentialPool, config: ApiConfig) -> Result<Self> {
+        Ok(Self {
+            http: config.build_client()?,
+            credentials,
+            config,
+            fields: FieldSelection::default(),
+        })
+    }
+
+    /// Creates the client with the token obtained from `get_bearer_token`
+    pub fn from_env(
+        bearer_token: Option<&str>,
+        env_file: Option<&str>,
+        config: ApiConfig,
+    ) -> Result<Self> {
+        let bearer_token = get_bearer_token(bearer_token, env_file)?;
+        Self::new(bearer_token, config)
+    }
+
+    /// Sets the fields and expansions requested for tweets
+    pub fn with_fields(mut self, fields: FieldSelection) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn config(&self) -> &ApiConfig {
+        &self.config
+    }
+
+    pub fn fields(&self) -> &FieldSelection {
+        &self.fields
+    }
+
+    pub fn credentials(&self) -> &CredentialPool {
+        &self.credentials
+    }
+
+    /// GET request with the parameters of `query`
+    pub(crate) async fn get<Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<Response> {
+        self.send(Method::GET, path, |o| o.query(query)).await
+    }
+
+    /// GET request that parses the JSON response
+    pub(crate) async fn get_json<T, Q>(&self, path: &str, query: &Q) -> Result<T>
+    where
+        T: DeserializeOwned,
+        Q: Serialize + ?Sized,
+    {
+        let res = self.get(path, query).await?.text().await?;
+        serde_json::from_str(&res).with_context(|| format!("Couldn't parse response:\n{}", res))
+    }
+
+    /// Sends the request built by `build` (it's called again for every retry)
+    /// with authorization and response timeout.
+    ///
+    /// Rate limited requests are retried with the next token of the pool, or
+    /// once the limit is reset if every token is exhausted. The same goes for
+    /// the tokens that reached the monthly tweet cap.
+    pub(crate) async fn send<F>(&self, method: Method, path: &str, build: F) -> Result<Response>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let endpoint = endpoint(path);
+        loop {
+            let (token, bearer_token) = match self.credentials.acquire(&endpoint)? {
+                Acquire::Token(token, bearer_token) => (token, bearer_token),
+                Acquire::Wait(wait) => {
+                    tokio::time::sleep(wait).await;
+                    continue;
+                }
+            };
+            let res = build(self.request(method.clone(), path, &bearer_token, true))
+                .send()
+                .await?;
+            let rate_limit = RateLimitHeaders::from_headers(res.headers())?;
+            self.credentials.update(token, &endpoint, &rate_limit);
+            let err = match check_response(res).await {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+            match err.downcast_ref::<TwitterApiError>() {
+                Some(TwitterApiError::RateLimited(_, rate_limit)) => {
+                    self.credentials.rate_limited(token, &endpoint, rate_limit)
+                }
+                Some(TwitterApiError::UsageCapExceeded(_)) => {
+                    // retried with the next token, unless it was the last one
+                    if !self.credentials.capped(token) {
+                        return Err(err);
+                    }
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+
+    /// GET request with authorization and without response timeout, it uses
+    /// the current token of the pool (the errors are handled by the caller)
+    pub(crate) async fn get_stream<Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<Response> {
+        let (token, bearer_token) = self.credentials.current();
+        let res = self
+            .request(Method::GET, path, &bearer_token, false)
+            .query(query)
+            .send()
+            .await?;
+        let rate_limit = RateLimitHeaders::from_headers(res.headers())?;
+        self.credentials.update(token, &endpoint(path), &rate_limit);
+        check_response(res).await
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        bearer_token: &str,
+        timeout: bool,
+    ) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, self.config.url(path))
+            .header(header::AUTHORIZATION, bearer_token);
+        match self.config.timeout {
+            Some(duration) if timeout => request.timeout(duration),
+            _ => request,
+        }
+    }
+}
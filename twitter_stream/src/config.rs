@@ -0,0 +1,59 @@
+use reqwest::Client;
+use std::time::Duration;
+
+pub const API_BASE_URL: &str = "https://api.twitter.com";
+pub const USER_AGENT: &str = concat!("twitter_stream/", env!("CARGO_PKG_VERSION"));
+
+/// Settings shared by every call made to the Twitter API.
+///
+/// Point `base_url` to a local server (eg: `http://127.0.0.1:8080`) to run
+/// against a mock of the API or a proxy.
+#[derive(Clone, Debug)]
+pub struct ApiConfig {
+    pub base_url: String,
+    /// Maximum time to wait while establishing a connection
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time to wait for a complete response, it is not applied to
+    /// streaming endpoints as they are expected to stay open
+    pub timeout: Option<Duration>,
+    /// Maximum time without receiving data from a stream before considering
+    /// the connection stalled
+    pub stall_timeout: Option<Duration>,
+    pub user_agent: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: API_BASE_URL.into(),
+            connect_timeout: Some(Duration::from_secs(10)),
+            timeout: Some(Duration::from_secs(30)),
+            stall_timeout: Some(Duration::from_secs(30)),
+            user_agent: USER_AGENT.into(),
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn new<T: Into<String>>(base_url: T) -> Self {
+        Self {
+            base_url: base_url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Joins `path` (eg: "/2/tweets/search/stream") to the base url
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    /// Builds the HTTP client, the response `timeout` is set per request
+    /// (see `TwitterClient`) as it can't be applied to streaming endpoints
+    pub fn build_client(&self) -> reqwest::Result<Client> {
+        let mut builder = Client::builder().user_agent(&self.user_agent);
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    pagination::{paginate, Page, Paginated},
+    search::{archive::MIN_REQUEST_INTERVAL, format_time},
+    TweetId, TwitterClient,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const COUNTS_RECENT_PATH: &str = "/2/tweets/counts/recent";
+pub const COUNTS_ALL_PATH: &str = "/2/tweets/counts/all";
+
+/// Size of the time buckets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub const ALL: &'static [Granularity] =
+        &[Granularity::Minute, Granularity::Hour, Granularity::Day];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Minute => "minute",
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+}
+
+impl std::fmt::Display for Granularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|o| o.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("invalid value {:?}, expected one of: minute, hour, day", s))
+    }
+}
+
+/// Query for the counts endpoints, it uses the same syntax as the search and the stream rules
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountsQuery {
+    pub query: String,
+    pub granularity: Granularity,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub since_id: Option<TweetId>,
+    pub until_id: Option<TweetId>,
+}
+
+impl CountsQuery {
+    pub fn new<T: Into<String>>(query: T, granularity: Granularity) -> Self {
+        Self {
+            query: query.into(),
+            granularity,
+            start_time: None,
+            end_time: None,
+            since_id: None,
+            until_id: None,
+        }
+    }
+
+    pub fn start_time(mut self, time: DateTime<Utc>) -> Self {
+        self.start_time = Some(time);
+        self
+    }
+
+    pub fn end_time(mut self, time: DateTime<Utc>) -> Self {
+        self.end_time = Some(time);
+        self
+    }
+
+    pub fn since_id(mut self, id: TweetId) -> Self {
+        self.since_id = Some(id);
+        self
+    }
+
+    pub fn until_id(mut self, id: TweetId) -> Self {
+        self.until_id = Some(id);
+        self
+    }
+
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("query", self.query.clone()),
+            ("granularity", self.granularity.to_string()),
+        ];
+        if let Some(time) = self.start_time {
+            query.push(("start_time", format_time(time)));
+        }
+        if let Some(time) = self.end_time {
+            query.push(("end_time", format_time(time)));
+        }
+        if let Some(id) = self.since_id {
+            query.push(("since_id", id.to_string()));
+        }
+        if let Some(id) = self.until_id {
+            query.push(("until_id", id.to_string()));
+        }
+        query
+    }
+}
+
+/// Number of tweets in a time bucket
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TweetCount {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub tweet_count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountsResponse {
+    #[serde(default)]
+    pub data: Vec<TweetCount>,
+    pub meta: CountsMeta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountsMeta {
+    pub total_tweet_count: usize,
+    pub next_token: Option<String>,
+}
+
+impl TwitterClient {
+    /// Number of tweets of the last 7 days matching the query
+    pub fn counts_recent(&self, query: CountsQuery) -> Paginated<TweetCount> {
+        self.counts(COUNTS_RECENT_PATH, query)
+    }
+
+    /// Number of tweets of the full archive matching the query
+    /// (Academic Research access only)
+    pub fn counts_all(&self, query: CountsQuery) -> Paginated<TweetCount> {
+        self.counts(COUNTS_ALL_PATH, query)
+    }
+
+    fn counts(&self, path: &'static str, query: CountsQuery) -> Paginated<TweetCount> {
+        let client = self.clone();
+        paginate(move |next_token| {
+            let client = client.clone();
+            let query = query.clone();
+            async move {
+                let mut params = query.to_query();
+                if let Some(token) = next_token {
+                    // the full-archive endpoints allow one request per second
+                    tokio::time::sleep(MIN_REQUEST_INTERVAL).await;
+                    params.push(("next_token", token));
+                }
+                let res = client.get_json::<CountsResponse, _>(path, &params).await?;
+                Ok(Page {
+                    items: res.data,
+                    next_token: res.meta.next_token,
+                })
+            }
+        })
+    }
+}
@@ -0,0 +1,272 @@
+//! Pool of bearer tokens (eg: of several projects), the requests keep using
+//! one token until it's rate limited or reaches the monthly tweet cap, then
+//! they move to the next one. The pool only waits when every token is
+//! exhausted.
+use crate::{api_error::DEFAULT_RATE_LIMIT_WAIT, stream_tweets::RateLimitHeaders};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Tokens file, eg:
+/// ```toml
+/// bearer_tokens = ["AAAA...", "BBBB..."]
+/// ```
+#[derive(Debug, Deserialize)]
+struct CredentialsFile {
+    bearer_tokens: Vec<String>,
+}
+
+/// Shared by the clones of `TwitterClient`, so the state of the tokens is
+/// known by every task using the client
+#[derive(Clone, Debug)]
+pub struct CredentialPool {
+    /// Values for the authorization header (eg: "Bearer XXXX")
+    tokens: Arc<Vec<String>>,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    current: usize,
+    /// When each endpoint can be used again with each token
+    exhausted: HashMap<(usize, String), Instant>,
+    /// Tokens that reached the monthly tweet cap
+    capped: HashSet<usize>,
+}
+
+/// Token to use for a request, or the time until one is available
+#[derive(Debug)]
+pub(crate) enum Acquire {
+    Token(usize, String),
+    Wait(Duration),
+}
+
+impl CredentialPool {
+    /// `tokens` are the values for the authorization header (eg: "Bearer XXXX")
+    pub fn new(tokens: Vec<String>) -> Result<Self> {
+        if tokens.is_empty() {
+            return Err(anyhow!("The credential pool needs at least one token"));
+        }
+        Ok(Self {
+            tokens: Arc::new(tokens),
+            state: Default::default(),
+        })
+    }
+
+    /// Reads `$BEARER_TOKEN_1`, `$BEARER_TOKEN_2`, ... (until the first one
+    /// that is not set), `None` if there is no `$BEARER_TOKEN_1`
+    pub fn from_env(env_file: Option<&str>) -> Option<Self> {
+        if let Some(env_file) = env_file {
+            dotenv::from_filename(env_file).ok();
+        }
+        let tokens = (1..)
+            .map_while(|i| std::env::var(format!("BEARER_TOKEN_{}", i)).ok())
+            .map(|o| format!("Bearer {}", o))
+            .collect();
+        Self::new(tokens).ok()
+    }
+
+    /// Reads the `bearer_tokens` list of a TOML file
+    pub fn from_toml<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let txt =
+            std::fs::read_to_string(path).with_context(|| format!("Couldn't read {:?}", path))?;
+        let file = toml::from_str::<CredentialsFile>(&txt)
+            .with_context(|| format!("Couldn't parse {:?}", path))?;
+        let tokens = file
+            .bearer_tokens
+            .into_iter()
+            .map(|o| format!("Bearer {}", o))
+            .collect();
+        Self::new(tokens).with_context(|| format!("No tokens found on {:?}", path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Token currently in use (the streams don't rotate)
+    pub(crate) fn current(&self) -> (usize, String) {
+        let i = self.state.lock().unwrap().current;
+        (i, self.tokens[i].clone())
+    }
+
+    /// First token (starting from the current one) that can be used for
+    /// `endpoint`, fails if every token reached the tweet cap
+    pub(crate) fn acquire(&self, endpoint: &str) -> Result<Acquire> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let mut wait = None;
+        for i in (0..self.len()).map(|o| (o + state.current) % self.len()) {
+            if state.capped.contains(&i) {
+                continue;
+            }
+            match state.exhausted.get(&(i, endpoint.to_string())) {
+                Some(&until) if until > now => {
+                    let remaining = until - now;
+                    wait = Some(wait.map_or(remaining, |o: Duration| o.min(remaining)));
+                }
+                _ => {
+                    state.current = i;
+                    return Ok(Acquire::Token(i, self.tokens[i].clone()));
+                }
+            }
+        }
+        wait.map(Acquire::Wait)
+            .ok_or_else(|| anyhow!("Every token reached the monthly tweet cap"))
+    }
+
+    /// Updates the state of the token with the headers of a response
+    pub(crate) fn update(&self, token: usize, endpoint: &str, rate_limit: &RateLimitHeaders) {
+        if let Some(wait) = rate_limit.duration_until_reset() {
+            self.exhaust(token, endpoint, wait);
+        }
+    }
+
+    /// The token got a 429 response for `endpoint`
+    pub(crate) fn rate_limited(&self, token: usize, endpoint: &str, rate_limit: &RateLimitHeaders) {
+        let wait = rate_limit
+            .duration_until_reset()
+            .unwrap_or(DEFAULT_RATE_LIMIT_WAIT);
+        self.exhaust(token, endpoint, wait);
+    }
+
+    /// The token reached the monthly tweet cap, returns `false` if it was
+    /// the last one available
+    pub(crate) fn capped(&self, token: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.capped.insert(token);
+        state.capped.len() < self.len()
+    }
+
+    fn exhaust(&self, token: usize, endpoint: &str, wait: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .exhausted
+            .insert((token, endpoint.to_string()), Instant::now() + wait);
+    }
+}
+
+/// Rate limits are set per endpoint, so the ids in the path are replaced
+/// (eg: "/2/users/:id/tweets"), the first segment is the version of the API
+pub(crate) fn endpoint(path: &str) -> String {
+    path.split('/')
+        .enumerate()
+        .map(|(i, o)| {
+            if i > 1 && !o.is_empty() && o.bytes().all(|b| b.is_ascii_digit()) {
+                ":id"
+            } else {
+                o
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const SEARCH: &str = "/2/tweets/search/recent";
+    const COUNTS: &str = "/2/tweets/counts/recent";
+
+    fn pool() -> CredentialPool {
+        CredentialPool::new(vec!["Bearer A".into(), "Bearer B".into()]).unwrap()
+    }
+
+    /// Headers of a response that used the last request of the window
+    fn exhausted(secs: u64) -> RateLimitHeaders {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        RateLimitHeaders {
+            limit: Some(450),
+            reset: Some(now + Duration::from_secs(secs)),
+            remaining: Some(0),
+        }
+    }
+
+    fn token(acquire: Acquire) -> usize {
+        match acquire {
+            Acquire::Token(i, _) => i,
+            Acquire::Wait(wait) => panic!("Expected a token, got a wait of {:?}", wait),
+        }
+    }
+
+    fn wait(acquire: Acquire) -> Duration {
+        match acquire {
+            Acquire::Wait(wait) => wait,
+            Acquire::Token(i, _) => panic!("Expected a wait, got the token {}", i),
+        }
+    }
+
+    #[test]
+    fn rotates_when_rate_limited() {
+        let pool = pool();
+        assert_eq!(token(pool.acquire(SEARCH).unwrap()), 0);
+        pool.rate_limited(0, SEARCH, &exhausted(30));
+        assert_eq!(token(pool.acquire(SEARCH).unwrap()), 1);
+        // the requests stay on the new token
+        assert_eq!(pool.current(), (1, "Bearer B".to_string()));
+        assert_eq!(token(pool.acquire(COUNTS).unwrap()), 1);
+    }
+
+    #[test]
+    fn limits_are_per_endpoint() {
+        let pool = pool();
+        pool.update(1, COUNTS, &exhausted(30));
+        pool.rate_limited(0, SEARCH, &exhausted(30));
+        assert_eq!(token(pool.acquire(SEARCH).unwrap()), 1);
+        assert_eq!(token(pool.acquire(COUNTS).unwrap()), 0);
+    }
+
+    #[test]
+    fn waits_when_every_token_is_exhausted() {
+        let pool = pool();
+        pool.rate_limited(0, SEARCH, &exhausted(300));
+        pool.update(1, SEARCH, &exhausted(30));
+        let delay = wait(pool.acquire(SEARCH).unwrap());
+        assert!(delay <= Duration::from_secs(30) && delay > Duration::from_secs(25));
+    }
+
+    #[test]
+    fn rate_limited_without_headers() {
+        let headers = RateLimitHeaders {
+            limit: None,
+            reset: None,
+            remaining: None,
+        };
+        let pool = pool();
+        pool.rate_limited(0, SEARCH, &headers);
+        pool.rate_limited(1, SEARCH, &headers);
+        let delay = wait(pool.acquire(SEARCH).unwrap());
+        assert!(delay <= DEFAULT_RATE_LIMIT_WAIT && delay > Duration::from_secs(55));
+    }
+
+    #[test]
+    fn skips_capped_tokens() {
+        let pool = pool();
+        assert!(pool.capped(0));
+        assert_eq!(token(pool.acquire(SEARCH).unwrap()), 1);
+        assert!(!pool.capped(1));
+        assert!(pool.acquire(SEARCH).is_err());
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(endpoint("/2/users/123/tweets"), "/2/users/:id/tweets");
+        assert_eq!(endpoint("/2/tweets/search/recent"), SEARCH);
+        assert_eq!(
+            endpoint("/2/users/by/username/jack"),
+            "/2/users/by/username/jack"
+        );
+    }
+}
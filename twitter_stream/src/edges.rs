@@ -0,0 +1,129 @@
+//! Edge lists (CSV) with the relations between users and tweets, they can be
+//! merged into the graph of `generate_graph` with `--extra-edges`.
+//!
+//! Users are identified by their username and tweets by their id, as the
+//! labels of the nodes of `generate_graph`.
+use crate::stream_tweets::{StreamResponse, UserData};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NodeClass {
+    User,
+    Tweet,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EdgeClass {
+    /// User -> tweet
+    TweetOwner,
+    /// Tweet -> referenced tweet (retweet, quote or reply)
+    ReferencedTweet,
+    /// Tweet -> mentioned user
+    UserMention,
+    /// Follower -> followed user
+    Follows,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Edge {
+    pub source: String,
+    pub source_class: NodeClass,
+    pub target: String,
+    pub target_class: NodeClass,
+    pub class: EdgeClass,
+}
+
+impl Edge {
+    pub fn follows(follower: &UserData, followed: &UserData) -> Self {
+        Self {
+            source: follower.username.clone(),
+            source_class: NodeClass::User,
+            target: followed.username.clone(),
+            target_class: NodeClass::User,
+            class: EdgeClass::Follows,
+        }
+    }
+
+    /// Owner, referenced tweets and mentions of a tweet (the owner is the
+    /// first user of the includes)
+    pub fn from_tweet(tweet: &StreamResponse) -> Vec<Self> {
+        let id = tweet.data.id.to_string();
+        let tweet_edge = |target: String, target_class, class| Self {
+            source: id.clone(),
+            source_class: NodeClass::Tweet,
+            target,
+            target_class,
+            class,
+        };
+
+        let mut edges = Vec::new();
+        if let Some(user) = tweet.includes.users.first() {
+            edges.push(Self {
+                source: user.username.clone(),
+                source_class: NodeClass::User,
+                target: id.clone(),
+                target_class: NodeClass::Tweet,
+                class: EdgeClass::TweetOwner,
+            });
+        }
+        for reference in tweet.data.referenced_tweets.iter().flatten() {
+            edges.push(tweet_edge(
+                reference.id.to_string(),
+                NodeClass::Tweet,
+                EdgeClass::ReferencedTweet,
+            ));
+        }
+        let mentions = tweet
+            .data
+            .entities
+            .iter()
+            .flat_map(|o| o.mentions.iter().flatten());
+        for mention in mentions {
+            edges.push(tweet_edge(
+                mention.username.clone(),
+                NodeClass::User,
+                EdgeClass::UserMention,
+            ));
+        }
+        edges
+    }
+}
+
+/// Appends edges to a CSV file (the header is written only for new files)
+pub struct EdgeWriter {
+    writer: csv::Writer<File>,
+}
+
+impl EdgeWriter {
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let new_file = !path.exists();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let writer = csv::WriterBuilder::new()
+            .has_headers(new_file)
+            .from_writer(file);
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, edge: &Edge) -> Result<()> {
+        self.writer.serialize(edge)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads an edge list written by `EdgeWriter`
+pub fn read_edges<P: AsRef<Path>>(path: P) -> Result<Vec<Edge>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let edges = reader.deserialize().collect::<Result<Vec<Edge>, _>>()?;
+    Ok(edges)
+}
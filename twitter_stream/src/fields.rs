@@ -0,0 +1,270 @@
+use std::collections::BTreeSet;
+
+/// Defines an enum for the values accepted by a query parameter of the API
+macro_rules! api_values {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $value:expr,)+ }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum $name {
+            $($variant,)+
+        }
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$variant,)+];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value,)+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.as_str())
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .find(|o| o.as_str() == s)
+                    .copied()
+                    .ok_or_else(|| {
+                        let valid = Self::ALL.iter().map(|o| o.as_str()).collect::<Vec<_>>();
+                        format!("invalid value {:?}, expected one of: {}", s, valid.join(", "))
+                    })
+            }
+        }
+    };
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/tweet
+    TweetField {
+        Attachments => "attachments",
+        AuthorId => "author_id",
+        ContextAnnotations => "context_annotations",
+        ConversationId => "conversation_id",
+        CreatedAt => "created_at",
+        Entities => "entities",
+        Geo => "geo",
+        Id => "id",
+        InReplyToUserId => "in_reply_to_user_id",
+        Lang => "lang",
+        NonPublicMetrics => "non_public_metrics",
+        OrganicMetrics => "organic_metrics",
+        PossiblySensitive => "possibly_sensitive",
+        PromotedMetrics => "promoted_metrics",
+        PublicMetrics => "public_metrics",
+        ReferencedTweets => "referenced_tweets",
+        ReplySettings => "reply_settings",
+        Source => "source",
+        Text => "text",
+        Withheld => "withheld",
+    }
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/expansions
+    Expansion {
+        AttachmentsPollIds => "attachments.poll_ids",
+        AttachmentsMediaKeys => "attachments.media_keys",
+        AuthorId => "author_id",
+        EntitiesMentionsUsername => "entities.mentions.username",
+        GeoPlaceId => "geo.place_id",
+        InReplyToUserId => "in_reply_to_user_id",
+        ReferencedTweetsId => "referenced_tweets.id",
+        ReferencedTweetsIdAuthorId => "referenced_tweets.id.author_id",
+    }
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/user
+    UserField {
+        CreatedAt => "created_at",
+        Description => "description",
+        Entities => "entities",
+        Id => "id",
+        Location => "location",
+        Name => "name",
+        PinnedTweetId => "pinned_tweet_id",
+        ProfileImageUrl => "profile_image_url",
+        Protected => "protected",
+        PublicMetrics => "public_metrics",
+        Url => "url",
+        Username => "username",
+        Verified => "verified",
+        Withheld => "withheld",
+    }
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/media
+    MediaField {
+        AltText => "alt_text",
+        DurationMs => "duration_ms",
+        Height => "height",
+        MediaKey => "media_key",
+        NonPublicMetrics => "non_public_metrics",
+        OrganicMetrics => "organic_metrics",
+        PreviewImageUrl => "preview_image_url",
+        PromotedMetrics => "promoted_metrics",
+        PublicMetrics => "public_metrics",
+        Type => "type",
+        Url => "url",
+        Width => "width",
+    }
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/place
+    PlaceField {
+        ContainedWithin => "contained_within",
+        Country => "country",
+        CountryCode => "country_code",
+        FullName => "full_name",
+        Geo => "geo",
+        Id => "id",
+        Name => "name",
+        PlaceType => "place_type",
+    }
+}
+
+api_values! {
+    /// https://developer.twitter.com/en/docs/twitter-api/data-dictionary/object-model/poll
+    PollField {
+        DurationMinutes => "duration_minutes",
+        EndDatetime => "end_datetime",
+        Id => "id",
+        Options => "options",
+        VotingStatus => "voting_status",
+    }
+}
+
+/// Fields and expansions requested to the API, the default selection is the
+/// one used by the stream since the first versions of this crate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSelection {
+    pub tweet_fields: BTreeSet<TweetField>,
+    pub expansions: BTreeSet<Expansion>,
+    pub user_fields: BTreeSet<UserField>,
+    pub media_fields: BTreeSet<MediaField>,
+    pub place_fields: BTreeSet<PlaceField>,
+    pub poll_fields: BTreeSet<PollField>,
+}
+
+impl Default for FieldSelection {
+    fn default() -> Self {
+        Self::empty()
+            .tweet_fields(vec![
+                TweetField::CreatedAt,
+                TweetField::ConversationId,
+                TweetField::ReferencedTweets,
+                TweetField::PublicMetrics,
+                TweetField::Entities,
+            ])
+            .expansion(Expansion::AuthorId)
+            .user_field(UserField::CreatedAt)
+    }
+}
+
+impl FieldSelection {
+    /// No fields, the API will return only the default ones (eg: tweet id and text)
+    pub fn empty() -> Self {
+        Self {
+            tweet_fields: BTreeSet::new(),
+            expansions: BTreeSet::new(),
+            user_fields: BTreeSet::new(),
+            media_fields: BTreeSet::new(),
+            place_fields: BTreeSet::new(),
+            poll_fields: BTreeSet::new(),
+        }
+    }
+
+    pub fn tweet_field(mut self, field: TweetField) -> Self {
+        self.tweet_fields.insert(field);
+        self
+    }
+
+    pub fn tweet_fields<I: IntoIterator<Item = TweetField>>(mut self, fields: I) -> Self {
+        self.tweet_fields.extend(fields);
+        self
+    }
+
+    pub fn expansion(mut self, expansion: Expansion) -> Self {
+        self.expansions.insert(expansion);
+        self
+    }
+
+    pub fn expansions<I: IntoIterator<Item = Expansion>>(mut self, expansions: I) -> Self {
+        self.expansions.extend(expansions);
+        self
+    }
+
+    pub fn user_field(mut self, field: UserField) -> Self {
+        self.user_fields.insert(field);
+        self
+    }
+
+    pub fn user_fields<I: IntoIterator<Item = UserField>>(mut self, fields: I) -> Self {
+        self.user_fields.extend(fields);
+        self
+    }
+
+    pub fn media_field(mut self, field: MediaField) -> Self {
+        self.media_fields.insert(field);
+        self
+    }
+
+    pub fn media_fields<I: IntoIterator<Item = MediaField>>(mut self, fields: I) -> Self {
+        self.media_fields.extend(fields);
+        self
+    }
+
+    pub fn place_field(mut self, field: PlaceField) -> Self {
+        self.place_fields.insert(field);
+        self
+    }
+
+    pub fn place_fields<I: IntoIterator<Item = PlaceField>>(mut self, fields: I) -> Self {
+        self.place_fields.extend(fields);
+        self
+    }
+
+    pub fn poll_field(mut self, field: PollField) -> Self {
+        self.poll_fields.insert(field);
+        self
+    }
+
+    pub fn poll_fields<I: IntoIterator<Item = PollField>>(mut self, fields: I) -> Self {
+        self.poll_fields.extend(fields);
+        self
+    }
+
+    /// Query parameters for the selection (empty groups are skipped)
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        fn join<T: std::fmt::Display>(values: &BTreeSet<T>) -> String {
+            values
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+
+        vec![
+            ("tweet.fields", join(&self.tweet_fields)),
+            ("expansions", join(&self.expansions)),
+            ("user.fields", join(&self.user_fields)),
+            ("media.fields", join(&self.media_fields)),
+            ("place.fields", join(&self.place_fields)),
+            ("poll.fields", join(&self.poll_fields)),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+    }
+}
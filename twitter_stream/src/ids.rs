@@ -0,0 +1,169 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+/// Defines a newtype for the 64 bit ids used by the API, they serialize as
+/// strings (like the `id_str` fields of the API) and deserialize from strings
+/// or numbers
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub u64);
+
+        impl $name {
+            pub fn as_u64(self) -> u64 {
+                self.0
+            }
+        }
+
+        impl From<u64> for $name {
+            fn from(id: u64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.trim().parse().map(Self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_any(IdVisitor).map(Self)
+            }
+        }
+    };
+}
+
+id_type! {
+    /// Id of a tweet
+    TweetId
+}
+
+id_type! {
+    /// Id of a user
+    UserId
+}
+
+id_type! {
+    /// Id of a filtered stream rule
+    RuleId
+}
+
+struct IdVisitor;
+
+impl<'de> de::Visitor<'de> for IdVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a 64 bit id as a string or a number")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        if v < 0 {
+            return Err(E::invalid_value(de::Unexpected::Signed(v), &self));
+        }
+        Ok(v as u64)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_tweets::StreamResponse;
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn serialize_as_strings() {
+        let ids = vec![TweetId(1390000000000000000), TweetId(u64::MAX)];
+        assert_eq!(
+            serde_json::to_string(&ids).unwrap(),
+            r#"["1390000000000000000","18446744073709551615"]"#
+        );
+        assert_eq!(serde_json::to_string(&RuleId(1)).unwrap(), r#""1""#);
+    }
+
+    #[test]
+    fn deserialize_strings_and_numbers() {
+        let id = |json: &str| serde_json::from_str::<RuleId>(json).map_err(|o| o.to_string());
+        assert_eq!(
+            id(r#""1390000000000000001""#),
+            Ok(RuleId(1390000000000000001))
+        );
+        assert_eq!(id("1390000000000000001"), Ok(RuleId(1390000000000000001)));
+        assert_eq!(id("18446744073709551615"), Ok(RuleId(u64::MAX)));
+        assert_eq!("  42 ".parse::<UserId>(), Ok(UserId(42)));
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        for json in &[
+            "-1",
+            r#""-1""#,
+            r#""abc""#,
+            r#""""#,
+            "1.5",
+            "null",
+            "18446744073709551616",
+        ] {
+            assert!(
+                serde_json::from_str::<TweetId>(json).is_err(),
+                "{} should be rejected",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn old_format_lines() {
+        // written before the typed ids: string ids and timestamps, numeric
+        // rule ids and the `url` of the tweet
+        let line = r#"{"data":{"id":"1390000000000000000","author_id":"2244994945","url":"https://twitter.com/i/web/status/1390000000000000000","text":"hello","created_at":"2021-05-14T15:00:00.000Z","conversation_id":"1390000000000000000","referenced_tweets":[{"id":"1389999999999999999","type":"quoted"}],"public_metrics":{"retweet_count":0,"reply_count":0,"like_count":1,"quote_count":0},"entities":null},"includes":{"users":[{"id":"2244994945","name":"Twitter Dev","username":"TwitterDev","created_at":"2013-12-14T04:35:55.000Z"}]},"matching_rules":[{"id":1390000000000000001,"tag":"test"}]}"#;
+        let tweet = serde_json::from_str::<StreamResponse>(line).unwrap();
+        let time = |o: &str| o.parse::<DateTime<Utc>>().ok();
+        assert_eq!(tweet.data.id, TweetId(1390000000000000000));
+        assert_eq!(tweet.data.author_id, Some(UserId(2244994945)));
+        assert_eq!(tweet.data.created_at, time("2021-05-14T15:00:00Z"));
+        assert_eq!(
+            tweet.includes.users[0].created_at,
+            time("2013-12-14T04:35:55Z")
+        );
+        let matching_rules = tweet.matching_rules.as_ref().unwrap();
+        assert_eq!(matching_rules[0].id, RuleId(1390000000000000001));
+
+        // written back with string ids
+        let json = serde_json::to_value(&tweet).unwrap();
+        assert_eq!(json["matching_rules"][0]["id"], "1390000000000000001");
+        assert_eq!(json["data"]["author_id"], "2244994945");
+    }
+}
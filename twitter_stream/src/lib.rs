@@ -1,10 +1,39 @@
+pub mod api_error;
+pub mod auth;
+pub mod client;
+pub mod config;
+pub mod counts;
+pub mod credentials;
+pub mod edges;
+pub mod fields;
+pub mod ids;
+pub mod lookup;
 pub mod opts;
+mod pagination;
 pub mod rules;
+pub mod search;
+pub mod snowflake;
 pub mod stream_tweets;
+pub mod users;
 
+pub use api_error::TwitterApiError;
+pub use auth::{authenticate, AppCredentials, TokenCache};
+pub use client::TwitterClient;
+pub use config::{ApiConfig, API_BASE_URL};
+pub use counts::{CountsQuery, Granularity, TweetCount};
+pub use credentials::CredentialPool;
+pub use fields::FieldSelection;
+pub use ids::{RuleId, TweetId, UserId};
 pub use opts::{Opts, SubCmd};
-pub use rules::{create_rule, delete_rule, delete_rules, get_rules, RULES_URL};
-pub use stream_tweets::{stream_data, StreamError, StreamResponse, STREAM_URL};
+pub use pagination::Paginated;
+pub use rules::{create_rule, delete_rule, delete_rules, get_rules, RULES_PATH};
+pub use search::{ArchiveSearch, SearchQuery, SEARCH_ALL_PATH, SEARCH_RECENT_PATH};
+pub use snowflake::Snowflake;
+pub use stream_tweets::{
+    sample_stream, stream_data, RawStreamResponse, ReconnectOptions, ResilientStream,
+    StreamEndpoint, StreamError, StreamEvent, StreamItem, StreamResponse, TweetStream,
+    SAMPLE_STREAM_PATH, STREAM_PATH,
+};
 
 use anyhow::{Context, Result};
 
@@ -0,0 +1,192 @@
+use crate::{
+    api_error::Problem,
+    stream_tweets::{
+        ResponseMeta, StreamResponse, StreamResponseIncludes, TweetsResponse, UserData,
+    },
+    TweetId, TwitterClient, UserId,
+};
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+pub const TWEETS_PATH: &str = "/2/tweets";
+pub const USERS_PATH: &str = "/2/users";
+pub const USERS_BY_PATH: &str = "/2/users/by";
+
+/// Maximum number of ids per lookup request
+pub const MAX_IDS_PER_REQUEST: usize = 100;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UsersResponse {
+    #[serde(default)]
+    pub data: Vec<UserData>,
+    #[serde(default)]
+    pub includes: StreamResponseIncludes,
+    #[serde(default)]
+    pub meta: ResponseMeta,
+    /// Ids or usernames that couldn't be found (eg: suspended accounts)
+    #[serde(default)]
+    pub errors: Vec<Problem>,
+}
+
+/// Number of objects added by `TwitterClient::hydrate`
+#[derive(Clone, Debug, Default)]
+pub struct HydrateSummary {
+    pub tweets: usize,
+    pub users: usize,
+}
+
+impl TwitterClient {
+    /// Looks up tweets by id (in batches of 100), the tweets that can't be
+    /// found (eg: deleted or protected) are skipped
+    pub async fn tweets(&self, ids: &[TweetId]) -> Result<Vec<StreamResponse>> {
+        let mut tweets = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+            let mut query = self.fields().to_query();
+            query.push(("ids", join(chunk)));
+            let res = self
+                .get_json::<TweetsResponse, _>(TWEETS_PATH, &query)
+                .await?;
+            tweets.extend(res.into_stream_responses());
+        }
+        Ok(tweets)
+    }
+
+    /// Looks up users by id (in batches of 100)
+    pub async fn users(&self, ids: &[UserId]) -> Result<Vec<UserData>> {
+        let mut users = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+            let mut query = self.user_fields_query();
+            query.push(("ids", join(chunk)));
+            let res = self
+                .get_json::<UsersResponse, _>(USERS_PATH, &query)
+                .await?;
+            users.extend(res.data);
+        }
+        Ok(users)
+    }
+
+    /// Looks up users by username (in batches of 100)
+    pub async fn users_by_username<T: AsRef<str>>(&self, usernames: &[T]) -> Result<Vec<UserData>> {
+        let mut users = Vec::with_capacity(usernames.len());
+        for chunk in usernames.chunks(MAX_IDS_PER_REQUEST) {
+            let mut query = self.user_fields_query();
+            let usernames = chunk.iter().map(|o| o.as_ref()).collect::<Vec<_>>();
+            query.push(("usernames", usernames.join(",")));
+            let res = self
+                .get_json::<UsersResponse, _>(USERS_BY_PATH, &query)
+                .await?;
+            users.extend(res.data);
+        }
+        Ok(users)
+    }
+
+    /// Fetches the referenced tweets that are not in `tweets` (adding them at the
+    /// end), and the authors and mentioned users that are not in the includes
+    /// (adding them to the includes of their tweets)
+    pub async fn hydrate(&self, tweets: &mut Vec<StreamResponse>) -> Result<HydrateSummary> {
+        let mut summary = HydrateSummary::default();
+
+        // 1. referenced tweets
+        let known = tweets.iter().map(|o| o.data.id).collect::<HashSet<_>>();
+        let mut missing = tweets
+            .iter()
+            .flat_map(|o| o.data.referenced_tweets.iter().flatten())
+            .map(|o| o.id)
+            .filter(|id| !known.contains(id))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        let found = self.tweets(&missing).await?;
+        summary.tweets = found.len();
+        tweets.extend(found);
+
+        // 2. authors, the author is always the first user of the includes
+        let mut by_id = tweets
+            .iter()
+            .flat_map(|o| &o.includes.users)
+            .map(|o| (o.id, o.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut missing = tweets
+            .iter()
+            .filter_map(|o| o.data.author_id)
+            .filter(|id| !by_id.contains_key(id))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        let found = self.users(&missing).await?;
+        summary.users += found.len();
+        by_id.extend(found.into_iter().map(|o| (o.id, o)));
+
+        for tweet in tweets.iter_mut() {
+            let first = tweet.includes.users.first().map(|o| o.id);
+            if let Some(author) = tweet.data.author_id.and_then(|id| by_id.get(&id)) {
+                if first != Some(author.id) {
+                    tweet.includes.users.retain(|o| o.id != author.id);
+                    tweet.includes.users.insert(0, author.clone());
+                }
+            }
+        }
+
+        // 3. mentioned users (usernames are case insensitive)
+        let mut users = tweets
+            .iter()
+            .flat_map(|o| &o.includes.users)
+            .map(|o| (o.username.to_lowercase(), o.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut missing = tweets
+            .iter()
+            .flat_map(mentions)
+            .map(|o| o.to_lowercase())
+            .filter(|o| !users.contains_key(o))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        let found = self.users_by_username(&missing).await?;
+        summary.users += found.len();
+        users.extend(found.into_iter().map(|o| (o.username.to_lowercase(), o)));
+
+        for tweet in tweets.iter_mut() {
+            // without the author the first user would be taken as the owner of the tweet
+            if tweet.includes.users.is_empty() {
+                continue;
+            }
+            let mentioned = mentions(tweet)
+                .filter_map(|o| users.get(&o.to_lowercase()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for user in mentioned {
+                if !tweet.includes.users.iter().any(|o| o.id == user.id) {
+                    tweet.includes.users.push(user);
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// The users endpoints don't accept the tweet fields or expansions
+    pub(crate) fn user_fields_query(&self) -> Vec<(&'static str, String)> {
+        self.fields()
+            .to_query()
+            .into_iter()
+            .filter(|(key, _)| *key == "user.fields")
+            .collect()
+    }
+}
+
+fn mentions(tweet: &StreamResponse) -> impl Iterator<Item = &str> {
+    tweet
+        .data
+        .entities
+        .iter()
+        .flat_map(|o| o.mentions.iter().flatten())
+        .map(|o| o.username.as_str())
+}
+
+fn join<T: ToString>(ids: &[T]) -> String {
+    ids.iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
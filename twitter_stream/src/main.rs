@@ -1,119 +1,202 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Clap;
 use console::{Style, Term};
 use dialoguer::{theme::ColorfulTheme, Confirm, Select};
 use futures::StreamExt;
-use std::{fs::OpenOptions, time::Instant};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::BufReader,
+    time::Instant,
+};
 use twitter_stream::{
-    create_rule, delete_rule, delete_rules, get_bearer_token, get_rules, stream_data, Opts,
-    StreamError, SubCmd,
+    edges::{Edge, EdgeWriter},
+    opts::MatchRules,
+    rules::{
+        lint, query::ParseError, CreateRuleError, Lint, Rule, RuleMatcher, RulesFile, SyncPlan,
+    },
+    stream_tweets::UserData,
+    AppCredentials, ArchiveSearch, CredentialPool, Opts, Paginated, ResilientStream, StreamError,
+    StreamEvent, StreamResponse, SubCmd, TweetCount, TwitterClient,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let opts = Opts::parse();
-    let bearer_token =
-        get_bearer_token(opts.bearer_token.as_deref(), Some(opts.env_file.as_str()))?;
 
-    match opts.subcmd {
+    match &opts.subcmd {
         // Do the Streaming
         None => {
-            let now = Instant::now();
-            let mut file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(true)
-                .open(&opts.file)?;
-
-            let term = Term::stdout();
+            let client = client(&opts).await?;
+            let options = opts.reconnect_options();
+            if opts.raw {
+                do_stream(ResilientStream::raw(client, options), &opts).await?;
+            } else {
+                do_stream(ResilientStream::new(client, options), &opts).await?;
+            }
+        }
+        Some(SubCmd::Search(search_opts)) if search_opts.archive => {
+            let client = client(&opts).await?;
+            let mut search = client.search_all(search_opts.query());
+            if let Some(path) = &search_opts.checkpoint {
+                search = search.with_checkpoint(path)?;
+            }
+            search_archive(search, &opts).await?;
+        }
+        Some(SubCmd::Search(search_opts)) => {
+            let client = client(&opts).await?;
+            let tweets = client.search_recent(search_opts.query());
+            save_tweets(tweets, &opts).await?;
+        }
+        Some(SubCmd::Counts(counts_opts)) => {
+            let client = client(&opts).await?;
+            let counts = if counts_opts.archive {
+                client.counts_all(counts_opts.query())
+            } else {
+                client.counts_recent(counts_opts.query())
+            };
+            let counts = counts.collect::<Vec<_>>().await;
+            let counts = counts.into_iter().collect::<Result<Vec<_>>>()?;
+            match &counts_opts.csv {
+                Some(path) => {
+                    write_counts(&counts, path)?;
+                    println!("Counts saved on: {}", path);
+                }
+                None => print_histogram(&counts),
+            }
+        }
+        Some(SubCmd::Hydrate(hydrate_opts)) => {
+            let client = client(&opts).await?;
             let bold = Style::new().bold();
-            println!("{}", bold.apply_to("Starting the stream..."));
-            let mut connection_resets = 0;
-            let mut processed = 0usize;
-            let mut errors = 0usize;
-            let mut finish = false;
             let green = Style::new().green();
-            let red = Style::new().red();
-
-            let (mut rate_limit, mut stream) = stream_data(&bearer_token).await?;
-            if opts.verbose > 0 {
-                println!("{:?}", rate_limit);
+            let (mut tweets, errors) = read_tweets(&hydrate_opts.input)?;
+            println!("Found {} tweets", green.apply_to(tweets.len()));
+            if errors > 0 {
+                println!(
+                    "Couldn't parse {} lines",
+                    Style::new().red().apply_to(errors)
+                );
             }
-            println!("\n");
 
-            while let Some(chunk) = stream.next().await {
-                match chunk {
-                    Ok(tweet_data) => {
-                        jsonl::write(&mut file, &tweet_data)?;
-                        processed += 1;
+            let summary = client.hydrate(&mut tweets).await?;
+            println!(
+                "Added {} tweets and {} users",
+                green.apply_to(summary.tweets),
+                green.apply_to(summary.users)
+            );
 
-                        let mut progress = format!("{}", processed);
-                        if let Some(limit) = opts.limit {
-                            progress.push_str(&format!("/{}", limit));
-                            if processed == limit {
-                                finish = true;
+            let output = hydrate_opts.output();
+            let mut file =
+                File::create(&output).with_context(|| format!("Couldn't create {:?}", output))?;
+            for tweet in &tweets {
+                jsonl::write(&mut file, tweet)?;
+            }
+            println!("Saved on: {}", bold.apply_to(output));
+        }
+        Some(SubCmd::Timelines(timelines_opts)) => {
+            let client = client(&opts).await?;
+            let (tweets, _) = read_tweets(&timelines_opts.input)?;
+            let users = included_users(&tweets);
+            let mut writer = EdgeWriter::append(&timelines_opts.output)?;
+            for (i, user) in users.iter().enumerate() {
+                let timeline = if timelines_opts.mentions {
+                    client.user_mentions(user.id)
+                } else {
+                    client.user_tweets(user.id)
+                };
+                let mut timeline = timeline.take(timelines_opts.max_tweets);
+                let mut edges = 0;
+                while let Some(tweet) = timeline.next().await {
+                    match tweet {
+                        Ok(tweet) => {
+                            for edge in Edge::from_tweet(&tweet) {
+                                writer.write(&edge)?;
+                                edges += 1;
                             }
                         }
-
-                        term.clear_last_lines(2)?;
-                        println!("Processed tweets  : {}", green.apply_to(progress));
-                        println!("Errors encountered: {}", red.apply_to(errors));
-                        if finish {
-                            break;
+                        Err(err) => {
+                            eprintln!("Couldn't read the tweets of {}: {}", user.username, err)
                         }
                     }
-                    Err(StreamError::SmallChunk) => {}
-                    Err(StreamError::Parse(err)) => {
-                        eprintln!(
-                            "Couldn't parse tweet data:\n{}\n{:?}\n\n",
-                            err.source, err.msg
-                        );
-                        errors += 1;
-                    }
-                    Err(StreamError::Reqwest(err)) => {
-                        if opts.verbose > 0 {
-                            eprintln!("Error reading chunk of data: {:#?}", err);
-                        }
-                        errors += 1;
-
-                        if let Some(max_resets) = opts.max_resets {
-                            if connection_resets >= max_resets {
-                                println!(
-                                    "Maximum number of connection resets ({}) reached...",
-                                    max_resets
-                                );
-                                break;
-                            }
+                }
+                writer.flush()?;
+                println!(
+                    "[{}/{}] {}: {} edges",
+                    i + 1,
+                    users.len(),
+                    user.username,
+                    edges
+                );
+            }
+            println!(
+                "Edges saved on: {}",
+                Style::new().bold().apply_to(&timelines_opts.output)
+            );
+        }
+        Some(SubCmd::Follows(follows_opts)) => {
+            let client = client(&opts).await?;
+            let (tweets, _) = read_tweets(&follows_opts.input)?;
+            let users = included_users(&tweets);
+            let mut writer = EdgeWriter::append(&follows_opts.output)?;
+            for (i, user) in users.iter().enumerate() {
+                let follows = if follows_opts.following {
+                    client.following(user.id)
+                } else {
+                    client.followers(user.id)
+                };
+                let mut follows = follows.take(follows_opts.max_users);
+                let mut edges = 0;
+                while let Some(other) = follows.next().await {
+                    match other {
+                        Ok(other) => {
+                            let edge = if follows_opts.following {
+                                Edge::follows(user, &other)
+                            } else {
+                                Edge::follows(&other, user)
+                            };
+                            writer.write(&edge)?;
+                            edges += 1;
                         }
-
-                        if let Some(rest) = rate_limit.duration_until_reset() {
-                            println!("Waiting for rate limit ({:?})...", rest);
-                            tokio::time::sleep(rest).await;
-                            println!("Resetting connection...\n\n");
+                        Err(err) => {
+                            eprintln!("Couldn't read the follows of {}: {}", user.username, err)
                         }
-
-                        let (rl, s) = stream_data(&bearer_token).await?;
-
-                        connection_resets += 1;
-                        rate_limit = rl;
-                        stream = s;
                     }
                 }
+                writer.flush()?;
+                println!(
+                    "[{}/{}] {}: {} edges",
+                    i + 1,
+                    users.len(),
+                    user.username,
+                    edges
+                );
             }
-
-            println!("Done :)\n{:?}", now.elapsed());
+            println!(
+                "Edges saved on: {}",
+                Style::new().bold().apply_to(&follows_opts.output)
+            );
         }
+        // Doesn't need (or request) a client token
+        Some(SubCmd::InvalidateToken) => invalidate_token(&opts).await?,
         Some(SubCmd::ListRules) => {
-            let rules = get_rules(&bearer_token).await?;
+            let client = client(&opts).await?;
+            let rules = client.rules().await?;
             println!("{}", rules);
         }
         Some(SubCmd::CreateRule(create_opts)) => {
-            let rule_str =
-                serde_json::to_string(&create_opts).context("Couldn't serialize rule")?;
-            let rule = create_rule(rule_str, &bearer_token).await?;
-            println!("{}", rule);
+            let client = client(&opts).await?;
+            let res = client.create_rules(create_opts.rules()?).await?;
+            for rule in &res.created {
+                println!("{}", rule);
+            }
+            print_rule_errors(&res.errors, "Couldn't create rule");
+            if !res.errors.is_empty() {
+                return Err(anyhow!("Couldn't create {} rules", res.errors.len()));
+            }
         }
         Some(SubCmd::DeleteRule(delete_opts)) => {
+            let client = client(&opts).await?;
             // Delete all rules if --all
             if delete_opts.all {
                 if delete_opts.force
@@ -122,10 +205,10 @@ async fn main() -> Result<()> {
                         .interact()
                         .unwrap()
                 {
-                    match get_rules(&bearer_token).await?.data {
+                    match client.rules().await?.data {
                         Some(ids) => {
                             let ids = ids.into_iter().map(|o| o.id).collect::<Vec<_>>();
-                            let n = delete_rules(ids, &bearer_token).await?;
+                            let n = client.delete_rules(ids).await?;
                             println!("All rules deleted ({})", n);
                         }
                         None => {
@@ -139,14 +222,14 @@ async fn main() -> Result<()> {
             // Prompt select if no id was given
             let mut id = delete_opts.id;
             if id.is_none() {
-                match get_rules(&bearer_token).await?.data {
+                match client.rules().await?.data {
                     Some(rules) => {
                         id = Select::with_theme(&ColorfulTheme::default())
                             .with_prompt("Pick the rule to delete")
                             .default(0)
                             .items(&rules)
                             .interact_opt()?
-                            .map(|i| rules[i].id.clone());
+                            .map(|i| rules[i].id);
                     }
                     None => {
                         println!("There are no rules in the stream")
@@ -156,11 +239,480 @@ async fn main() -> Result<()> {
 
             // Delete 1 rule
             if let Some(id) = id {
-                delete_rule(&id, &bearer_token).await?;
-                println!("Rule {:?} deleted", id);
+                client.delete_rules(vec![id]).await?;
+                println!("Rule {} deleted", id);
+            }
+        }
+        Some(SubCmd::ValidateRule(validate_opts)) => {
+            let client = client(&opts).await?;
+            let rules = validate_opts.rules()?;
+            let mut invalid = 0;
+            // only the rules without local errors are sent to twitter
+            let mut checked = Vec::new();
+            for rule in rules {
+                let lints = lint(&rule.value, validate_opts.access_level);
+                print_lints(&rule.value, &lints);
+                if lints.iter().any(|o| o.is_error()) {
+                    invalid += 1;
+                } else {
+                    checked.push(rule);
+                }
+            }
+            if !checked.is_empty() {
+                let res = client.validate_rules(checked).await?;
+                for rule in &res.created {
+                    println!(
+                        "{} {:?}",
+                        Style::new().green().apply_to("Valid rule"),
+                        rule.value.as_deref().unwrap_or("")
+                    );
+                }
+                print_rule_errors(&res.errors, "Invalid rule");
+                invalid += res.errors.len();
+            }
+            if invalid > 0 {
+                return Err(anyhow!("Found {} invalid rules", invalid));
+            }
+        }
+        Some(SubCmd::MatchRules(match_opts)) => {
+            // Doesn't need a client with the rules given as arguments or on a file
+            let matcher = match match_opts.rules()? {
+                Some(rules) => RuleMatcher::from_specs(rules),
+                None => {
+                    let rules = client(&opts).await?.rules().await?.data;
+                    match rules {
+                        Some(rules) if !rules.is_empty() => RuleMatcher::new(rules),
+                        _ => return Err(anyhow!("There are no rules in the stream")),
+                    }
+                }
+            }
+            .map_err(rule_parse_error)?;
+            match_rules(&matcher, match_opts)?;
+        }
+        Some(SubCmd::SyncRules(sync_opts)) => {
+            let client = client(&opts).await?;
+            let desired = RulesFile::load(&sync_opts.file)?.rules;
+            let plan = client.plan_rules(&desired).await?;
+            print_plan(&plan);
+            if sync_opts.dry_run || plan.is_empty() {
+                return Ok(());
+            }
+            if !plan.delete.is_empty()
+                && !sync_opts.force
+                && !Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "Do you want to delete {} rules?",
+                        plan.delete.len()
+                    ))
+                    .interact()?
+            {
+                return Ok(());
+            }
+            let summary = client.apply_rules(&plan).await?;
+            println!(
+                "Rules synchronized: {} added, {} deleted",
+                summary.created.len(),
+                summary.deleted
+            );
+            print_rule_errors(&summary.errors, "Couldn't create rule");
+            if !summary.errors.is_empty() {
+                if summary.deleted == 0 && !plan.delete.is_empty() {
+                    eprintln!("The rules of the stream were not deleted");
+                }
+                return Err(anyhow!("Couldn't create {} rules", summary.errors.len()));
             }
         }
     }
 
     Ok(())
 }
+
+fn print_lints(rule: &str, lints: &[Lint]) {
+    for lint in lints {
+        let style = if lint.is_error() {
+            Style::new().red()
+        } else {
+            Style::new().yellow()
+        };
+        eprintln!("{}\n{}\n", style.apply_to(lint), lint.underline(rule));
+    }
+}
+
+fn print_rule_errors(errors: &[CreateRuleError], label: &str) {
+    let red = Style::new().red();
+    for error in errors {
+        eprintln!("{} {}", red.apply_to(label), error);
+    }
+}
+
+fn print_plan(plan: &SyncPlan) {
+    let green = Style::new().green();
+    let red = Style::new().red();
+    for rule in &plan.unchanged {
+        println!("  {}", rule);
+    }
+    for rule in &plan.delete {
+        println!("{}", red.apply_to(format!("- {}", rule)));
+    }
+    for rule in &plan.add {
+        println!("{}", green.apply_to(format!("+ {}", rule)));
+    }
+    println!(
+        "Plan: {} to add, {} to delete, {} unchanged",
+        green.apply_to(plan.add.len()),
+        red.apply_to(plan.delete.len()),
+        plan.unchanged.len()
+    );
+}
+
+/// Client authenticated with `--credentials`, `--bearer-token` or the env file
+async fn client(opts: &Opts) -> Result<TwitterClient> {
+    let client = match &opts.credentials {
+        Some(path) => {
+            TwitterClient::with_credentials(CredentialPool::from_toml(path)?, opts.api_config())?
+        }
+        None => {
+            TwitterClient::authenticate(
+                opts.bearer_token.as_deref(),
+                Some(opts.env_file.as_str()),
+                Some(&opts.token_cache()),
+                opts.api_config(),
+            )
+            .await?
+        }
+    };
+    Ok(client.with_fields(opts.fields()))
+}
+
+fn rule_parse_error((rule, err): (Rule, ParseError)) -> anyhow::Error {
+    anyhow!(
+        "Couldn't parse rule {:?}: {}",
+        rule.value.as_deref().unwrap_or(""),
+        err
+    )
+}
+
+/// Tags the tweets of the input with the rules they match and shows the
+/// number of tweets matched by each rule
+fn match_rules(matcher: &RuleMatcher, match_opts: &MatchRules) -> Result<()> {
+    let bold = Style::new().bold();
+    let green = Style::new().green();
+    let yellow = Style::new().yellow();
+    for (rule, query) in matcher.rules() {
+        let unsupported = query
+            .unsupported_operators()
+            .iter()
+            .map(|o| format!("{}:", o.name()))
+            .collect::<Vec<_>>();
+        if !unsupported.is_empty() {
+            eprintln!(
+                "{} {} can't be evaluated locally on rule {:?}, it only counts the tweets \
+                 it matches regardless of them",
+                yellow.apply_to("warning:"),
+                unsupported.join(", "),
+                rule.value.as_deref().unwrap_or("")
+            );
+        }
+    }
+
+    // the input can be large, the tweets are matched while reading them
+    let input = &match_opts.input;
+    let file = File::open(input).with_context(|| format!("Couldn't open {:?}", input))?;
+    let mut reader = BufReader::new(file);
+    let mut output = match &match_opts.output {
+        Some(path) => {
+            Some(File::create(path).with_context(|| format!("Couldn't create {:?}", path))?)
+        }
+        None => None,
+    };
+    let mut total = 0;
+    let mut errors = 0;
+    let mut matched = 0;
+    let mut hits = HashMap::new();
+    loop {
+        let mut tweet = match jsonl::read::<_, StreamResponse>(&mut reader) {
+            Ok(tweet) => tweet,
+            Err(jsonl::ReadError::Eof) => break,
+            Err(jsonl::ReadError::Deserialize(_)) => {
+                errors += 1;
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        total += 1;
+        if !matcher.tag(&mut tweet) {
+            continue;
+        }
+        matched += 1;
+        for rule in tweet.matching_rules.iter().flatten() {
+            *hits.entry(rule.id).or_insert(0usize) += 1;
+        }
+        if let Some(output) = &mut output {
+            jsonl::write(output, &tweet)?;
+        }
+    }
+
+    println!("Found {} tweets", green.apply_to(total));
+    if errors > 0 {
+        println!(
+            "Couldn't parse {} lines",
+            Style::new().red().apply_to(errors)
+        );
+    }
+    let percent = |n: usize| match total {
+        0 => 0.0,
+        total => 100.0 * n as f64 / total as f64,
+    };
+    for (rule, _) in matcher.rules() {
+        let n = hits.get(&rule.id).copied().unwrap_or(0);
+        println!("{:>8} {:>6.1}%  {}", green.apply_to(n), percent(n), rule);
+    }
+    println!(
+        "Matched by any rule: {} ({:.1}%)",
+        bold.apply_to(matched),
+        percent(matched)
+    );
+    if let Some(path) = &match_opts.output {
+        println!("Saved on: {}", bold.apply_to(path));
+    }
+    Ok(())
+}
+
+/// Writes the tweets to `opts.file` while showing the progress
+async fn do_stream<T: Serialize>(mut stream: ResilientStream<T>, opts: &Opts) -> Result<()> {
+    let now = Instant::now();
+    let mut file = open_file(&opts.file)?;
+
+    let term = Term::stdout();
+    let bold = Style::new().bold();
+    println!("{}", bold.apply_to("Starting the stream..."));
+    let mut processed = 0usize;
+    let mut errors = 0usize;
+    let mut finish = false;
+    let green = Style::new().green();
+    let red = Style::new().red();
+    println!("\n");
+
+    while let Some(event) = stream.next().await {
+        match event {
+            Ok(StreamEvent::Tweet(tweet_data)) => {
+                jsonl::write(&mut file, &tweet_data)?;
+                processed += 1;
+
+                let mut progress = format!("{}", processed);
+                if let Some(limit) = opts.limit {
+                    progress.push_str(&format!("/{}", limit));
+                    if processed == limit {
+                        finish = true;
+                    }
+                }
+
+                term.clear_last_lines(2)?;
+                println!("Processed tweets  : {}", green.apply_to(progress));
+                println!("Errors encountered: {}", red.apply_to(errors));
+                if finish {
+                    break;
+                }
+            }
+            Ok(StreamEvent::Error(StreamError::Parse(err))) => {
+                eprintln!(
+                    "Couldn't parse tweet data:\n{}\n{:?}\n\n",
+                    err.source, err.msg
+                );
+                errors += 1;
+            }
+            Ok(StreamEvent::Error(err)) => {
+                if opts.verbose > 0 {
+                    eprintln!("Error reading the stream: {:#?}", err);
+                }
+                errors += 1;
+            }
+            Ok(StreamEvent::Connected { rate_limit, .. }) => {
+                if opts.verbose > 0 {
+                    println!("{:?}\n\n", rate_limit);
+                }
+            }
+            Ok(StreamEvent::Reconnecting { wait, reason, .. }) => {
+                if opts.verbose > 0 {
+                    eprintln!("Connection lost: {}", reason);
+                }
+                errors += 1;
+                println!("Resetting connection in {:?}...\n\n", wait);
+            }
+            Err(err) => {
+                println!("{}...", err);
+                break;
+            }
+        }
+    }
+
+    println!("Done :)\n{:?}", now.elapsed());
+    Ok(())
+}
+
+/// Writes the tweets to `opts.file` until there are no more results or `opts.limit` is reached
+async fn save_tweets(mut tweets: Paginated<StreamResponse>, opts: &Opts) -> Result<()> {
+    let now = Instant::now();
+    let mut file = open_file(&opts.file)?;
+
+    let term = Term::stdout();
+    let bold = Style::new().bold();
+    let green = Style::new().green();
+    println!("{}", bold.apply_to("Searching tweets..."));
+    println!();
+
+    let mut processed = 0usize;
+    while let Some(tweet) = tweets.next().await {
+        jsonl::write(&mut file, &tweet?)?;
+        processed += 1;
+        term.clear_last_lines(1)?;
+        println!("Saved tweets: {}", green.apply_to(processed));
+        if Some(processed) == opts.limit {
+            break;
+        }
+    }
+
+    println!("Done :)\n{:?}", now.elapsed());
+    Ok(())
+}
+
+/// Writes the pages of the full-archive search to `opts.file`, saving the
+/// checkpoint after each one
+async fn search_archive(mut search: ArchiveSearch, opts: &Opts) -> Result<()> {
+    let now = Instant::now();
+    let mut file = open_file(&opts.file)?;
+
+    let term = Term::stdout();
+    let bold = Style::new().bold();
+    let green = Style::new().green();
+    let checkpoint = search.checkpoint();
+    if checkpoint.pages > 0 {
+        println!(
+            "{} (from page {}, {} tweets saved)",
+            bold.apply_to("Resuming full-archive search..."),
+            checkpoint.pages,
+            checkpoint.tweets
+        );
+    } else {
+        println!("{}", bold.apply_to("Searching the full archive..."));
+    }
+    println!("\n");
+
+    let mut processed = 0usize;
+    while let Some(tweets) = search.next_page().await? {
+        let remaining = opts.limit.map_or(tweets.len(), |o| o - processed);
+        for tweet in tweets.iter().take(remaining) {
+            jsonl::write(&mut file, tweet)?;
+            processed += 1;
+        }
+        if tweets.len() > remaining {
+            // resuming requests the page again and skips the saved tweets
+            search.save_partial_checkpoint(remaining)?;
+            break;
+        }
+        search.save_checkpoint()?;
+
+        let checkpoint = search.checkpoint();
+        term.clear_last_lines(2)?;
+        println!("Saved tweets: {}", green.apply_to(processed));
+        println!(
+            "Pages       : {} (total tweets: {})",
+            checkpoint.pages, checkpoint.tweets
+        );
+        if Some(processed) == opts.limit {
+            break;
+        }
+    }
+
+    println!("Done :)\n{:?}", now.elapsed());
+    Ok(())
+}
+
+async fn invalidate_token(opts: &Opts) -> Result<()> {
+    let credentials = AppCredentials::from_env(Some(opts.env_file.as_str()))
+        .context("$API_KEY and $API_SECRET are needed to invalidate a token")?;
+    let cache = opts.token_cache();
+    let cached = cache.load(&credentials)?;
+    let token = match (&opts.bearer_token, &cached) {
+        (Some(token), _) | (None, Some(token)) => token,
+        (None, None) => anyhow::bail!("No token found on {:?}", cache.path()),
+    };
+    credentials
+        .invalidate_token(token, &opts.api_config())
+        .await?;
+    if cached.as_ref() == Some(token) {
+        cache.remove()?;
+    }
+    println!("Token invalidated");
+    Ok(())
+}
+
+fn print_histogram(counts: &[TweetCount]) {
+    const WIDTH: usize = 50;
+    let bold = Style::new().bold();
+    let green = Style::new().green();
+    let max = counts.iter().map(|o| o.tweet_count).max().unwrap_or(0);
+    for count in counts {
+        let bar = match max {
+            0 => 0,
+            max => (count.tweet_count * WIDTH + max / 2) / max,
+        };
+        println!(
+            "{} {} {}",
+            count.start.format("%Y-%m-%d %H:%M"),
+            green.apply_to("\u{2587}".repeat(bar)),
+            count.tweet_count
+        );
+    }
+    let total = counts.iter().map(|o| o.tweet_count).sum::<usize>();
+    println!("Total tweets: {}", bold.apply_to(total));
+}
+
+fn write_counts(counts: &[TweetCount], path: &str) -> Result<()> {
+    let mut writer =
+        csv::Writer::from_path(path).with_context(|| format!("Couldn't create {:?}", path))?;
+    for count in counts {
+        writer.serialize(count)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a JSON Lines file of tweets, returns the number of lines that couldn't be parsed
+fn read_tweets(path: &str) -> Result<(Vec<StreamResponse>, usize)> {
+    let file = File::open(path).with_context(|| format!("Couldn't open {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut tweets = Vec::new();
+    let mut errors = 0;
+    loop {
+        match jsonl::read::<_, StreamResponse>(&mut reader) {
+            Ok(tweet) => tweets.push(tweet),
+            Err(jsonl::ReadError::Eof) => break,
+            Err(jsonl::ReadError::Deserialize(_)) => errors += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok((tweets, errors))
+}
+
+/// Users of the includes of the tweets (without duplicates)
+fn included_users(tweets: &[StreamResponse]) -> Vec<UserData> {
+    let mut users = tweets
+        .iter()
+        .flat_map(|o| &o.includes.users)
+        .map(|o| (o.id, o))
+        .collect::<HashMap<_, _>>()
+        .into_values()
+        .cloned()
+        .collect::<Vec<_>>();
+    users.sort_by(|a, b| a.username.cmp(&b.username));
+    users
+}
+
+fn open_file(path: &str) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Couldn't open {:?}", path))
+}
@@ -1,5 +1,14 @@
+use crate::{
+    fields::{Expansion, MediaField, PlaceField, PollField, TweetField, UserField},
+    rules::{AccessLevel, RuleSpec, RulesFile},
+    ApiConfig, CountsQuery, FieldSelection, Granularity, ReconnectOptions, RuleId, SearchQuery,
+    StreamEndpoint, TokenCache, TweetId,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use clap::{AppSettings, Clap};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Clap, Debug)]
 #[clap(
@@ -14,39 +23,155 @@ pub struct Opts {
     #[clap(short, long, default_value = "twitter_data.jsonl")]
     pub file: String,
     /// Token for twitter authentification, if not given the program
-    /// will look for the environment variable BEARER_TOKEN.
+    /// will look for the environment variables BEARER_TOKEN_1, BEARER_TOKEN_2,
+    /// ..., then BEARER_TOKEN, or request one with API_KEY and API_SECRET.
     #[clap(short, long)]
     pub bearer_token: Option<String>,
-    /// Enviroment file to look for $BEARER_TOKEN.
+    /// Enviroment file to look for $BEARER_TOKEN_1..N, $BEARER_TOKEN (or $API_KEY and
+    /// $API_SECRET).
     #[clap(long, default_value = ".env")]
     pub env_file: String,
+    /// TOML file with a list of tokens to rotate through when one is rate
+    /// limited (eg: bearer_tokens = ["XXXX", "YYYY"]), they can also be set
+    /// on the environment as BEARER_TOKEN_1, BEARER_TOKEN_2, ... The tokens
+    /// are taken from (in order): this file, --bearer-token, BEARER_TOKEN_1..N,
+    /// BEARER_TOKEN, and API_KEY with API_SECRET.
+    #[clap(long)]
+    pub credentials: Option<String>,
+    /// File to keep the token obtained with $API_KEY and $API_SECRET
+    #[clap(long, default_value = ".bearer_token.json")]
+    pub token_cache: String,
     /// Maximum number of connection resets while streaming
     #[clap(short, long)]
     pub max_resets: Option<usize>,
+    /// Recover the tweets sent while the stream was disconnected (up to 5 minutes),
+    /// requires Academic Research access
+    #[clap(long)]
+    pub backfill: bool,
+    /// Save the tweets exactly as they were sent by twitter, without parsing
+    /// them (all the fields are kept, even the ones not modeled by this crate)
+    #[clap(long)]
+    pub raw: bool,
+    /// Stream a random sample of about 1% of all the tweets instead of
+    /// the tweets matching the rules
+    #[clap(long)]
+    pub sample: bool,
+    /// Base url of the Twitter API, change it to use a proxy or a local mock server
+    #[clap(long, default_value = "https://api.twitter.com")]
+    pub api_base_url: String,
+    /// Seconds without receiving data (tweets or keep-alive signals)
+    /// before resetting the stream connection
+    #[clap(long, default_value = "30")]
+    pub stall_timeout: u64,
+    /// Tweet field to request besides the default ones (can be repeated),
+    /// eg: --tweet-field lang --tweet-field source
+    #[clap(long = "tweet-field", number_of_values = 1)]
+    pub tweet_fields: Vec<TweetField>,
+    /// Expansion to request besides the default ones (can be repeated)
+    #[clap(long = "expansion", number_of_values = 1)]
+    pub expansions: Vec<Expansion>,
+    /// User field to request besides the default ones (can be repeated)
+    #[clap(long = "user-field", number_of_values = 1)]
+    pub user_fields: Vec<UserField>,
+    /// Media field to request (can be repeated), needs the expansion "attachments.media_keys"
+    #[clap(long = "media-field", number_of_values = 1)]
+    pub media_fields: Vec<MediaField>,
+    /// Place field to request (can be repeated), needs the expansion "geo.place_id"
+    #[clap(long = "place-field", number_of_values = 1)]
+    pub place_fields: Vec<PlaceField>,
+    /// Poll field to request (can be repeated), needs the expansion "attachments.poll_ids"
+    #[clap(long = "poll-field", number_of_values = 1)]
+    pub poll_fields: Vec<PollField>,
+    /// Don't request the default fields (created_at, conversation_id, referenced_tweets,
+    /// public_metrics, entities, author_id expansion and user created_at)
+    #[clap(long)]
+    pub no_default_fields: bool,
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: i32,
     #[clap(subcommand)]
     pub subcmd: Option<SubCmd>,
 }
 
+impl Opts {
+    pub fn token_cache(&self) -> TokenCache {
+        TokenCache::new(&self.token_cache)
+    }
+
+    pub fn api_config(&self) -> ApiConfig {
+        ApiConfig {
+            stall_timeout: Some(Duration::from_secs(self.stall_timeout)),
+            ..ApiConfig::new(&self.api_base_url)
+        }
+    }
+
+    pub fn reconnect_options(&self) -> ReconnectOptions {
+        ReconnectOptions {
+            max_resets: self.max_resets,
+            backfill: self.backfill,
+            endpoint: if self.sample {
+                StreamEndpoint::Sample
+            } else {
+                StreamEndpoint::Filtered
+            },
+        }
+    }
+
+    pub fn fields(&self) -> FieldSelection {
+        let fields = if self.no_default_fields {
+            FieldSelection::empty()
+        } else {
+            FieldSelection::default()
+        };
+        fields
+            .tweet_fields(self.tweet_fields.iter().copied())
+            .expansions(self.expansions.iter().copied())
+            .user_fields(self.user_fields.iter().copied())
+            .media_fields(self.media_fields.iter().copied())
+            .place_fields(self.place_fields.iter().copied())
+            .poll_fields(self.poll_fields.iter().copied())
+    }
+}
+
 #[derive(Clap, Debug)]
 pub enum SubCmd {
     /// List current stream rules
     ListRules,
     CreateRule(CreateRule),
     DeleteRule(DeleteRule),
+    SyncRules(SyncRules),
+    ValidateRule(ValidateRule),
+    MatchRules(MatchRules),
+    Search(Search),
+    Counts(Counts),
+    Hydrate(Hydrate),
+    Timelines(Timelines),
+    Follows(Follows),
+    /// Invalidates the token obtained with $API_KEY and $API_SECRET (the cached
+    /// one, or the one given with --bearer-token) and removes it from the cache
+    InvalidateToken,
 }
 
-/// Creates a rule on the current stream
-#[derive(Clap, Debug, Serialize, Deserialize)]
+/// Creates rules on the current stream
+#[derive(Clap, Debug)]
 #[clap(
     after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule"
 )]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub struct CreateRule {
-    pub value: String,
+    /// Rules to create
+    pub values: Vec<String>,
+    /// Tag for the rules given as arguments
     #[clap(short, long)]
     pub tag: Option<String>,
+    /// Rules file (.toml or .yaml, see `sync-rules`) with more rules to create
+    #[clap(long)]
+    pub file: Option<String>,
+}
+
+impl CreateRule {
+    pub fn rules(&self) -> Result<Vec<RuleSpec>> {
+        rule_specs(&self.values, self.tag.as_deref(), self.file.as_deref())
+    }
 }
 
 /// Delete a rule on the current stream
@@ -56,9 +181,254 @@ pub struct CreateRule {
 )]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub struct DeleteRule {
-    pub id: Option<String>,
+    pub id: Option<RuleId>,
     #[clap(short, long)]
     pub all: bool,
     #[clap(short, long)]
     pub force: bool,
 }
+
+/// Makes the stream rules match a rules file (.toml or .yaml with a list of
+/// `rules`, each one with a `value` and an optional `tag`)
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct SyncRules {
+    /// Rules file
+    pub file: String,
+    /// Only print the plan
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Don't ask for confirmation before deleting rules
+    #[clap(short, long)]
+    pub force: bool,
+}
+
+/// Checks rules without creating them, first locally and then with twitter
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct ValidateRule {
+    /// Rules to validate
+    pub values: Vec<String>,
+    /// Rules file (.toml or .yaml, see `sync-rules`) with more rules to validate
+    #[clap(long)]
+    pub file: Option<String>,
+    /// Access level of the project: standard or academic
+    #[clap(long, default_value = "standard")]
+    pub access_level: AccessLevel,
+}
+
+impl ValidateRule {
+    pub fn rules(&self) -> Result<Vec<RuleSpec>> {
+        rule_specs(&self.values, None, self.file.as_deref())
+    }
+}
+
+/// Evaluates rules locally against the tweets of a JSON Lines file and shows
+/// how many tweets each rule matches
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct MatchRules {
+    /// JSON Lines file with `StreamResponse` items
+    pub input: String,
+    /// Rules to match, by default the rules of the stream
+    pub values: Vec<String>,
+    /// Rules file (.toml or .yaml, see `sync-rules`) with more rules to match
+    #[clap(long)]
+    pub file: Option<String>,
+    /// Writes the matched tweets to this file, with their simulated `matching_rules`
+    #[clap(short, long)]
+    pub output: Option<String>,
+}
+
+impl MatchRules {
+    /// Rules given as arguments or on a file, `None` to use the rules of the stream
+    pub fn rules(&self) -> Result<Option<Vec<RuleSpec>>> {
+        if self.values.is_empty() && self.file.is_none() {
+            return Ok(None);
+        }
+        rule_specs(&self.values, None, self.file.as_deref()).map(Some)
+    }
+}
+
+/// Searches the tweets of the last 7 days (or the full archive) and saves them on `--file`
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/search/api-reference/get-tweets-search-recent"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct Search {
+    /// Search query, it uses the same syntax as the stream rules
+    pub query: String,
+    /// Oldest time of the tweets, eg: 2021-05-14T15:00:00Z
+    #[clap(long)]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Newest time of the tweets, eg: 2021-05-14T15:00:00Z
+    #[clap(long)]
+    pub end_time: Option<DateTime<Utc>>,
+    /// Only tweets more recent than this id
+    #[clap(long)]
+    pub since_id: Option<TweetId>,
+    /// Only tweets older than this id
+    #[clap(long)]
+    pub until_id: Option<TweetId>,
+    /// Results per request (10 to 100, or 500 with --archive)
+    #[clap(long, default_value = "100")]
+    pub max_results: usize,
+    /// Search the full archive instead of the last 7 days,
+    /// requires Academic Research access
+    #[clap(long)]
+    pub archive: bool,
+    /// File to save the progress of the full-archive search, if it exists
+    /// the search resumes from the last saved page
+    #[clap(long, requires = "archive")]
+    pub checkpoint: Option<String>,
+}
+
+impl Search {
+    pub fn query(&self) -> SearchQuery {
+        SearchQuery {
+            query: self.query.clone(),
+            start_time: self.start_time,
+            end_time: self.end_time,
+            since_id: self.since_id,
+            until_id: self.until_id,
+            max_results: Some(self.max_results),
+        }
+    }
+}
+
+/// Shows the number of tweets matching a rule, to check its volume before creating it
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/counts/api-reference/get-tweets-counts-recent"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct Counts {
+    /// Rule to count (same syntax as `create-rule`)
+    pub value: String,
+    /// Size of the time buckets: minute, hour or day
+    #[clap(short, long, default_value = "hour")]
+    pub granularity: Granularity,
+    /// Oldest time of the tweets, eg: 2021-05-14T15:00:00Z
+    #[clap(long)]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Newest time of the tweets, eg: 2021-05-14T15:00:00Z
+    #[clap(long)]
+    pub end_time: Option<DateTime<Utc>>,
+    /// Count on the full archive instead of the last 7 days,
+    /// requires Academic Research access
+    #[clap(long)]
+    pub archive: bool,
+    /// Write the counts to a CSV file instead of showing the histogram
+    #[clap(long)]
+    pub csv: Option<String>,
+}
+
+impl Counts {
+    pub fn query(&self) -> CountsQuery {
+        CountsQuery {
+            query: self.value.clone(),
+            granularity: self.granularity,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            since_id: None,
+            until_id: None,
+        }
+    }
+}
+
+/// Fetches the referenced tweets, authors and mentioned users missing from a
+/// JSON Lines file, so `generate_graph` can draw all the edges
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/lookup/api-reference/get-tweets"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct Hydrate {
+    /// JSON Lines file with `StreamResponse` items
+    pub input: String,
+    /// Output file, by default `<input>_hydrated.jsonl`
+    #[clap(short, long)]
+    pub output: Option<String>,
+}
+
+impl Hydrate {
+    pub fn output(&self) -> String {
+        match &self.output {
+            Some(output) => output.clone(),
+            None => {
+                let stem = self.input.strip_suffix(".jsonl").unwrap_or(&self.input);
+                format!("{}_hydrated.jsonl", stem)
+            }
+        }
+    }
+}
+
+/// Collects the tweets (or mentions) of the users of a JSON Lines file as an
+/// edge list, merge it with `generate_graph --extra-edges`
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/tweets/timelines/introduction"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct Timelines {
+    /// JSON Lines file with `StreamResponse` items, the users of its includes are collected
+    pub input: String,
+    /// Edge list (CSV), new edges are appended
+    #[clap(short, long, default_value = "timeline_edges.csv")]
+    pub output: String,
+    /// Collect the tweets mentioning the users instead of the tweets of the users
+    #[clap(long)]
+    pub mentions: bool,
+    /// Maximum number of tweets per user
+    #[clap(long, default_value = "100")]
+    pub max_tweets: usize,
+}
+
+/// Collects the followers (or the following) of the users of a JSON Lines file
+/// as an edge list, merge it with `generate_graph --extra-edges`
+#[derive(Clap, Debug)]
+#[clap(
+    after_help = "See: https://developer.twitter.com/en/docs/twitter-api/users/follows/introduction"
+)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct Follows {
+    /// JSON Lines file with `StreamResponse` items, the users of its includes are collected
+    pub input: String,
+    /// Edge list (CSV), new edges are appended
+    #[clap(short, long, default_value = "follows_edges.csv")]
+    pub output: String,
+    /// Collect the users followed by the users instead of their followers
+    #[clap(long)]
+    pub following: bool,
+    /// Maximum number of followers (or following) per user
+    #[clap(long, default_value = "1000")]
+    pub max_users: usize,
+}
+
+/// Rules given as arguments (with the same tag) and the ones of the rules file
+fn rule_specs(values: &[String], tag: Option<&str>, file: Option<&str>) -> Result<Vec<RuleSpec>> {
+    let mut rules = values
+        .iter()
+        .map(|value| RuleSpec {
+            value: value.clone(),
+            tag: tag.map(String::from),
+        })
+        .collect::<Vec<_>>();
+    if let Some(path) = file {
+        rules.extend(RulesFile::load(path)?.rules);
+    }
+    if rules.is_empty() {
+        return Err(anyhow!("No rules given, pass some values or a --file"));
+    }
+    Ok(rules)
+}
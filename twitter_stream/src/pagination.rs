@@ -0,0 +1,34 @@
+use anyhow::Result;
+use futures::{stream, Future, Stream, StreamExt};
+use std::pin::Pin;
+
+/// Stream of items of a paginated endpoint, it ends after the first error
+pub type Paginated<T> = Pin<Box<dyn Stream<Item = Result<T>> + Send>>;
+
+/// Items of one request to a paginated endpoint and the token for the next one
+pub(crate) struct Page<T> {
+    pub items: Vec<T>,
+    pub next_token: Option<String>,
+}
+
+/// Calls `fetch` with the token of the next page (`None` for the first one)
+/// until there are no more pages or a request fails.
+pub(crate) fn paginate<T, F, Fut>(fetch: F) -> Paginated<T>
+where
+    T: Send + 'static,
+    F: FnMut(Option<String>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Page<T>>> + Send,
+{
+    let pages = stream::unfold((fetch, Some(None)), |(mut fetch, token)| async move {
+        let token = token?;
+        match fetch(token).await {
+            Ok(page) => {
+                let items = page.items.into_iter().map(Ok).collect::<Vec<_>>();
+                let next = page.next_token.map(Some);
+                Some((stream::iter(items), (fetch, next)))
+            }
+            Err(err) => Some((stream::iter(vec![Err(err)]), (fetch, None))),
+        }
+    });
+    Box::pin(pages.flatten())
+}
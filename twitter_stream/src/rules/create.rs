@@ -1,34 +1,69 @@
-use super::{ResponseRuleMeta, Rule, RULES_URL};
+use super::{ResponseRuleMeta, Rule, RuleSpec, RULES_PATH};
+use crate::{ApiConfig, RuleId, TwitterClient};
 use anyhow::{anyhow, Context, Result};
-use reqwest::header;
-use serde::Deserialize;
-
-pub async fn create_rule(rule: String, bearer_token: &str) -> Result<Rule> {
-    let client = reqwest::Client::new();
-    let res = client
-        .post(RULES_URL)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::AUTHORIZATION, bearer_token)
-        .body(format!("{{\"add\": [{}]}}", rule))
-        .send()
-        .await?
-        .text()
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+pub async fn create_rule(rule: RuleSpec, bearer_token: &str, config: &ApiConfig) -> Result<Rule> {
+    let mut res = TwitterClient::new(bearer_token, config.clone())?
+        .create_rules(vec![rule])
         .await?;
+    if let Some(error) = res.errors.pop() {
+        return Err(anyhow!("Couldn't create rule: {}", error));
+    }
+    match res.created.len() {
+        1 => Ok(res.created.remove(0)),
+        n => Err(anyhow!("Expected to create 1 rule, got {}", n)),
+    }
+}
 
-    let res = serde_json::from_str::<CreateRuleResponse>(&res).with_context(|| {
-        format!(
-            "Couldn't parse response:\n{}",
-            serde_json::to_string_pretty(&res).unwrap_or(res)
-        )
-    })?;
+/// Body of the request to add rules
+#[derive(Debug, Serialize)]
+pub struct AddRulesRequest<'a> {
+    pub add: &'a [RuleSpec],
+}
 
-    if let Some(error) = res.errors {
-        return Err(anyhow!("Error creating rule: {:#?}", error));
+/// Rules created by `TwitterClient::create_rules` and the ones that were
+/// rejected (eg: invalid or duplicated rules)
+#[derive(Clone, Debug, Default)]
+pub struct CreateRulesResult {
+    pub created: Vec<Rule>,
+    pub errors: Vec<CreateRuleError>,
+}
+
+impl TwitterClient {
+    /// Adds rules to the stream in one request, each rule is created or
+    /// rejected on its own
+    pub async fn create_rules(&self, rules: Vec<RuleSpec>) -> Result<CreateRulesResult> {
+        self.add_rules(&rules, false).await
+    }
+
+    /// Checks the rules with twitter without creating them (`created` has
+    /// the valid rules)
+    pub async fn validate_rules(&self, rules: Vec<RuleSpec>) -> Result<CreateRulesResult> {
+        self.add_rules(&rules, true).await
     }
 
-    match &res.meta.summary.get("created") {
-        Some(1) => Ok(res.data.unwrap()[0].clone()),
-        _ => Err(anyhow!("Couldn't create rule: {:#?}", res)),
+    async fn add_rules(&self, rules: &[RuleSpec], dry_run: bool) -> Result<CreateRulesResult> {
+        let body = AddRulesRequest { add: rules };
+        let query: &[_] = if dry_run { &[("dry_run", true)] } else { &[] };
+        let res = self
+            .send(Method::POST, RULES_PATH, |o| o.query(query).json(&body))
+            .await?
+            .text()
+            .await?;
+
+        let res = serde_json::from_str::<CreateRuleResponse>(&res).with_context(|| {
+            format!(
+                "Couldn't parse response:\n{}",
+                serde_json::to_string_pretty(&res).unwrap_or(res)
+            )
+        })?;
+
+        Ok(CreateRulesResult {
+            created: res.data.unwrap_or_default(),
+            errors: res.errors.unwrap_or_default(),
+        })
     }
 }
 
@@ -39,11 +74,78 @@ pub struct CreateRuleResponse {
     pub meta: ResponseRuleMeta,
 }
 
-#[derive(Debug, Deserialize)]
+/// Rule that couldn't be created, or an error of the whole request (eg: the
+/// limit of rules was reached) without `value`
+#[derive(Clone, Debug, Deserialize)]
 pub struct CreateRuleError {
-    pub value: String,
+    pub value: Option<String>,
+    /// Id of the existing rule with the same value (for duplicated rules)
+    pub id: Option<RuleId>,
+    #[serde(default)]
     pub details: Vec<String>,
+    pub detail: Option<String>,
     pub title: String,
     #[serde(rename = "type")]
     pub error_type: String,
 }
+
+impl std::fmt::Display for CreateRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(value) = &self.value {
+            write!(f, "{:?}: ", value)?;
+        }
+        write!(f, "{}", self.title)?;
+        if !self.details.is_empty() {
+            write!(f, " ({})", self.details.join(", "))?;
+        } else if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_rules_body() {
+        let rules = vec![
+            RuleSpec {
+                value: r#""say \"hi\"" C:\temp"#.into(),
+                tag: Some("quotes \"and\" backslashes".into()),
+            },
+            RuleSpec {
+                value: "cat".into(),
+                tag: None,
+            },
+        ];
+        let body = serde_json::to_string(&AddRulesRequest { add: &rules }).unwrap();
+        assert_eq!(
+            body,
+            r#"{"add":[{"value":"\"say \\\"hi\\\"\" C:\\temp","tag":"quotes \"and\" backslashes"},{"value":"cat"}]}"#
+        );
+        // the body is valid JSON with the same values
+        let parsed = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(parsed["add"][0]["value"], rules[0].value.as_str());
+    }
+
+    #[test]
+    fn request_errors() {
+        let res = r#"{
+            "meta": {"sent": "2021-05-14T15:00:00.000Z"},
+            "errors": [{
+                "title": "RulesCapExceeded",
+                "detail": "Rule creation would exceed the limit of 25 rules",
+                "type": "https://api.twitter.com/2/problems/rule-cap"
+            }]
+        }"#;
+        let res = serde_json::from_str::<CreateRuleResponse>(res).unwrap();
+        let error = &res.errors.unwrap()[0];
+        assert_eq!(error.value, None);
+        assert_eq!(
+            error.to_string(),
+            "RulesCapExceeded (Rule creation would exceed the limit of 25 rules)"
+        );
+    }
+}
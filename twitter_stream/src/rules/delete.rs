@@ -1,56 +1,61 @@
-use super::{RULES_URL, ResponseRuleMeta};
-use anyhow::{Context, Result, anyhow};
-use reqwest::header;
-use serde::Deserialize;
-
-pub async fn delete_rule(id: &str, bearer_token: &str) -> Result<()> {
-    let client = reqwest::Client::new();
-    let res = client
-        .post(RULES_URL)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::AUTHORIZATION, bearer_token)
-        .body(format!("{{\"delete\": {{ \"ids\": [ {:?} ] }} }}", id))
-        .send()
-        .await?
-        .text()
+use super::{ResponseRuleMeta, RULES_PATH};
+use crate::{ApiConfig, RuleId, TwitterClient};
+use anyhow::{anyhow, Context, Result};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+pub async fn delete_rule(id: RuleId, bearer_token: &str, config: &ApiConfig) -> Result<()> {
+    TwitterClient::new(bearer_token, config.clone())?
+        .delete_rules(vec![id])
         .await?;
+    Ok(())
+}
 
-    let res = serde_json::from_str::<DeleteRuleResponse>(&res).with_context(|| {
-        format!(
-            "Couldn't parse response:\n{}",
-            serde_json::to_string_pretty(&res).unwrap_or(res)
-        )
-    })?;
+pub async fn delete_rules(
+    ids: Vec<RuleId>,
+    bearer_token: &str,
+    config: &ApiConfig,
+) -> Result<usize> {
+    TwitterClient::new(bearer_token, config.clone())?
+        .delete_rules(ids)
+        .await
+}
 
-    match &res.meta.summary.get("deleted") {
-        Some(1) => Ok(()),
-        _ => Err(anyhow!("Couldn't delete rule: {:#?}", res)),
-    }
+/// Body of the request to delete rules
+#[derive(Debug, Serialize)]
+pub struct DeleteRulesRequest<'a> {
+    pub delete: DeleteIds<'a>,
 }
 
-pub async fn delete_rules(ids: Vec<String>, bearer_token: &str) -> Result<usize> {
-    let client = reqwest::Client::new();
-    let res = client
-        .post(RULES_URL)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::AUTHORIZATION, bearer_token)
-        .body(format!("{{\"delete\": {{ \"ids\": {:?} }} }}", ids))
-        .send()
-        .await?
-        .text()
-        .await?;
+#[derive(Debug, Serialize)]
+pub struct DeleteIds<'a> {
+    pub ids: &'a [RuleId],
+}
 
-    let res = serde_json::from_str::<DeleteRuleResponse>(&res).with_context(|| {
-        format!(
-            "Couldn't parse response:\n{}",
-            serde_json::to_string_pretty(&res).unwrap_or(res)
-        )
-    })?;
-
-    let n = ids.len();
-    match &res.meta.summary.get("deleted") {
-        Some(&i) if i == n => Ok(n),
-        _ => Err(anyhow!("Couldn't delete all the rules: {:#?}", res)),
+impl TwitterClient {
+    /// Deletes rules from the stream, returns the number of deleted rules
+    pub async fn delete_rules(&self, ids: Vec<RuleId>) -> Result<usize> {
+        let body = DeleteRulesRequest {
+            delete: DeleteIds { ids: &ids },
+        };
+        let res = self
+            .send(Method::POST, RULES_PATH, |o| o.json(&body))
+            .await?
+            .text()
+            .await?;
+
+        let res = serde_json::from_str::<DeleteRuleResponse>(&res).with_context(|| {
+            format!(
+                "Couldn't parse response:\n{}",
+                serde_json::to_string_pretty(&res).unwrap_or(res)
+            )
+        })?;
+
+        let n = ids.len();
+        match &res.meta.summary.get("deleted") {
+            Some(&i) if i == n => Ok(n),
+            _ => Err(anyhow!("Couldn't delete all the rules: {:#?}", res)),
+        }
     }
 }
 
@@ -58,3 +63,20 @@ pub async fn delete_rules(ids: Vec<String>, bearer_token: &str) -> Result<usize>
 pub struct DeleteRuleResponse {
     pub meta: ResponseRuleMeta,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delete_rules_body() {
+        let ids = vec![RuleId(1390000000000000001), RuleId(2)];
+        let body = DeleteRulesRequest {
+            delete: DeleteIds { ids: &ids },
+        };
+        assert_eq!(
+            serde_json::to_string(&body).unwrap(),
+            r#"{"delete":{"ids":["1390000000000000001","2"]}}"#
+        );
+    }
+}
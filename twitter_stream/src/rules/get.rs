@@ -1,24 +1,26 @@
-use super::{Rule, RULES_URL};
+use super::{Rule, RULES_PATH};
+use crate::{ApiConfig, TwitterClient};
 use anyhow::{Context, Result};
-use reqwest::header;
 use serde::Deserialize;
 
-pub async fn get_rules(bearer_token: &str) -> Result<ListRulesResponse> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(RULES_URL)
-        .header(header::AUTHORIZATION, bearer_token)
-        .send()
-        .await?
-        .text()
-        .await?;
+pub async fn get_rules(bearer_token: &str, config: &ApiConfig) -> Result<ListRulesResponse> {
+    TwitterClient::new(bearer_token, config.clone())?
+        .rules()
+        .await
+}
+
+impl TwitterClient {
+    /// Lists the rules of the stream
+    pub async fn rules(&self) -> Result<ListRulesResponse> {
+        let res = self.get(RULES_PATH, &()).await?.text().await?;
 
-    serde_json::from_str::<ListRulesResponse>(&res).with_context(|| {
-        format!(
-            "Couldn't parse response:\n{}",
-            serde_json::to_string_pretty(&res).unwrap_or(res)
-        )
-    })
+        serde_json::from_str::<ListRulesResponse>(&res).with_context(|| {
+            format!(
+                "Couldn't parse response:\n{}",
+                serde_json::to_string_pretty(&res).unwrap_or(res)
+            )
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,185 @@
+//! Splits the value of a rule into tokens keeping their position, so the
+//! problems found by the linter (or the parser) can point at the rule text.
+use std::fmt;
+
+/// Byte range of a token in the rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Span covering both spans
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Keyword, emoji, #hashtag, @mention or $cashtag
+    Word(String),
+    /// "Exact phrase" (without the quotes)
+    Phrase(String),
+    /// `name:value`, the value can be quoted (eg: place:"new york city") or
+    /// between brackets (eg: point_radius:[2.355128 48.861118 16km])
+    Operator {
+        name: String,
+        value: String,
+    },
+    Or,
+    /// `-` before a term, operator or group
+    Negation,
+    LParen,
+    RParen,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LexError {
+    pub span: Span,
+    pub kind: LexErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A quote without its closing quote
+    UnterminatedQuote,
+    /// An operator value starting with `[` without its closing bracket
+    UnclosedBracket,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LexErrorKind::UnterminatedQuote => write!(f, "Unterminated quote"),
+            LexErrorKind::UnclosedBracket => write!(f, "Unclosed bracket"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+pub fn tokenize(rule: &str) -> Result<Vec<Token>, LexError> {
+    let mut tokens = Vec::new();
+    let mut chars = rule.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let kind = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                TokenKind::LParen
+            }
+            ')' => {
+                chars.next();
+                TokenKind::RParen
+            }
+            '-' => {
+                chars.next();
+                TokenKind::Negation
+            }
+            '"' => TokenKind::Phrase(read_phrase(rule, start, &mut chars)?),
+            _ => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &rule[start..end];
+                match word.find(':') {
+                    Some(i) if is_operator_name(&word[..i]) => {
+                        let name = word[..i].to_string();
+                        let value = match chars.peek() {
+                            // quoted value, eg: url:"https://..."
+                            Some(&(i, '"')) if end == i && word.ends_with(':') => {
+                                read_phrase(rule, i, &mut chars)?
+                            }
+                            // coordinates, eg: bounding_box:[-105.3 39.9 -105.1 40.0]
+                            _ if word[i + 1..].starts_with('[') && !word.contains(']') => {
+                                read_brackets(rule, start + i + 1, &mut chars)?
+                            }
+                            _ => word[i + 1..].to_string(),
+                        };
+                        TokenKind::Operator { name, value }
+                    }
+                    _ if word == "OR" => TokenKind::Or,
+                    _ => TokenKind::Word(word.to_string()),
+                }
+            }
+        };
+        let end = chars.peek().map(|&(i, _)| i).unwrap_or_else(|| rule.len());
+        // the whitespace after the token is not part of it
+        let end = start + rule[start..end].trim_end().len();
+        tokens.push(Token {
+            kind,
+            span: Span::new(start, end),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Reads a quoted text starting at `start`, a backslash escapes the
+/// next character
+fn read_phrase(
+    rule: &str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+) -> Result<String, LexError> {
+    chars.next();
+    let mut phrase = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' => return Ok(phrase),
+            '\\' => {
+                if let Some((_, c)) = chars.next() {
+                    phrase.push(c);
+                }
+            }
+            c => phrase.push(c),
+        }
+    }
+    Err(LexError {
+        span: Span::new(start, rule.len()),
+        kind: LexErrorKind::UnterminatedQuote,
+    })
+}
+
+/// Reads up to the closing bracket of a value starting at `start`, the
+/// value keeps the brackets
+fn read_brackets(
+    rule: &str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+) -> Result<String, LexError> {
+    for (i, c) in chars {
+        if c == ']' {
+            return Ok(rule[start..=i].to_string());
+        }
+    }
+    Err(LexError {
+        span: Span::new(start, rule.len()),
+        kind: LexErrorKind::UnclosedBracket,
+    })
+}
+
+/// Operator names are lowercase letters and underscores (eg: "bio_location"),
+/// anything else with a colon is a keyword (eg: "10:30")
+fn is_operator_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+}
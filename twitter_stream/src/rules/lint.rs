@@ -0,0 +1,446 @@
+//! Local checks of a rule before sending it to twitter, see:
+//! https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/build-a-rule
+use super::lexer::{tokenize, Span, Token, TokenKind};
+use std::{fmt, str::FromStr};
+
+/// Access level of the project, it sets the length of the rules and the
+/// operators that can be used
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessLevel {
+    Standard,
+    Academic,
+}
+
+impl AccessLevel {
+    /// Maximum number of characters of a rule
+    pub fn max_rule_length(self) -> usize {
+        match self {
+            AccessLevel::Standard => 512,
+            AccessLevel::Academic => 1024,
+        }
+    }
+}
+
+impl FromStr for AccessLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(AccessLevel::Standard),
+            "academic" => Ok(AccessLevel::Academic),
+            _ => Err(format!(
+                "Invalid access level {:?}, expected standard or academic",
+                s
+            )),
+        }
+    }
+}
+
+/// Operators of the filtered stream
+pub const OPERATORS: &[&str] = &[
+    "from",
+    "to",
+    "url",
+    "retweets_of",
+    "context",
+    "entity",
+    "conversation_id",
+    "bio",
+    "bio_name",
+    "bio_location",
+    "place",
+    "place_country",
+    "point_radius",
+    "bounding_box",
+    "is",
+    "has",
+    "lang",
+    "sample",
+];
+
+/// Operators that need Academic Research access
+pub const ACADEMIC_OPERATORS: &[&str] = &[
+    "bio",
+    "bio_name",
+    "bio_location",
+    "place",
+    "place_country",
+    "point_radius",
+    "bounding_box",
+];
+
+pub const IS_VALUES: &[&str] = &["retweet", "reply", "quote", "verified", "nullcast"];
+pub const HAS_VALUES: &[&str] = &[
+    "hashtags", "cashtags", "links", "mentions", "media", "images", "videos", "geo",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Twitter would reject the rule
+    Error,
+    /// The rule is valid but probably doesn't do what was intended
+    Warning,
+}
+
+/// Problem found in a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Lint {
+    fn error<T: Into<String>>(span: Span, message: T) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+        }
+    }
+
+    fn warning<T: Into<String>>(span: Span, message: T) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The rule with the span of the problem underlined, eg:
+    /// ```text
+    /// cat (dog
+    ///     ^
+    /// ```
+    pub fn underline(&self, rule: &str) -> String {
+        let start = rule[..self.span.start].chars().count();
+        let len = rule[self.span.start..self.span.end].chars().count().max(1);
+        format!("{}\n{}{}", rule, " ".repeat(start), "^".repeat(len))
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Checks the syntax of the rule, its length and the operators available
+/// with `access`
+pub fn lint(rule: &str, access: AccessLevel) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    let length = rule.chars().count();
+    if length > access.max_rule_length() {
+        let start = rule
+            .char_indices()
+            .nth(access.max_rule_length())
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        lints.push(Lint::error(
+            Span::new(start, rule.len()),
+            format!(
+                "The rule has {} characters, the limit is {}",
+                length,
+                access.max_rule_length()
+            ),
+        ));
+    }
+
+    let tokens = match tokenize(rule) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            lints.push(Lint::error(err.span, err.to_string()));
+            return lints;
+        }
+    };
+    if tokens.is_empty() {
+        lints.push(Lint::error(Span::new(0, rule.len()), "The rule is empty"));
+        return lints;
+    }
+
+    check_parentheses(&tokens, &mut lints);
+    check_negations(rule, &tokens, &mut lints);
+    check_or(&tokens, &mut lints);
+    for token in &tokens {
+        check_token(token, access, &mut lints);
+    }
+    lints.sort_by_key(|o| o.span.start);
+    lints
+}
+
+fn check_parentheses(tokens: &[Token], lints: &mut Vec<Lint>) {
+    let mut open = Vec::new();
+    for token in tokens {
+        match token.kind {
+            TokenKind::LParen => open.push(token.span),
+            TokenKind::RParen => match open.pop() {
+                Some(_) => {}
+                None => lints.push(Lint::error(token.span, "Unmatched closing parenthesis")),
+            },
+            _ => {}
+        }
+    }
+    for span in open {
+        lints.push(Lint::error(span, "Unclosed parenthesis"));
+    }
+}
+
+fn check_negations(rule: &str, tokens: &[Token], lints: &mut Vec<Lint>) {
+    let mut depth = 0usize;
+    let mut negated_depth = None;
+    let mut positive = false;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Negation => {
+                let next = tokens.get(i + 1);
+                let attached = next
+                    .map(|o| o.span.start == token.span.end)
+                    .unwrap_or(false);
+                let negatable = next
+                    .map(|o| !matches!(o.kind, TokenKind::Or | TokenKind::RParen))
+                    .unwrap_or(false);
+                if !attached || !negatable {
+                    lints.push(Lint::error(
+                        token.span,
+                        "Bare negation, the minus sign must be attached to a term (eg: -cat)",
+                    ));
+                } else if negated_depth.is_none() {
+                    if let Some(TokenKind::LParen) = next.map(|o| &o.kind) {
+                        negated_depth = Some(depth);
+                    }
+                }
+            }
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => {
+                depth = depth.saturating_sub(1);
+                if negated_depth == Some(depth) {
+                    negated_depth = None;
+                }
+            }
+            TokenKind::Or => {}
+            _ => {
+                let negated = i > 0 && tokens[i - 1].kind == TokenKind::Negation;
+                if !negated && negated_depth.is_none() {
+                    positive = true;
+                }
+            }
+        }
+    }
+    if !positive {
+        lints.push(Lint::error(
+            Span::new(0, rule.trim_end().len()),
+            "The rule must have at least one term that is not negated",
+        ));
+    }
+}
+
+fn check_or(tokens: &[Token], lints: &mut Vec<Lint>) {
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Or {
+            continue;
+        }
+        let before = i.checked_sub(1).map(|i| &tokens[i].kind);
+        let after = tokens.get(i + 1).map(|o| &o.kind);
+        let missing_before = matches!(
+            before,
+            None | Some(TokenKind::Or) | Some(TokenKind::LParen) | Some(TokenKind::Negation)
+        );
+        let missing_after = matches!(after, None | Some(TokenKind::Or) | Some(TokenKind::RParen));
+        if missing_before || missing_after {
+            lints.push(Lint::error(token.span, "OR needs a term on each side"));
+        }
+    }
+}
+
+fn check_token(token: &Token, access: AccessLevel, lints: &mut Vec<Lint>) {
+    match &token.kind {
+        TokenKind::Word(word) if word == "or" => lints.push(Lint::warning(
+            token.span,
+            "Lowercase \"or\" matches the word, use OR for a logical OR",
+        )),
+        TokenKind::Word(word) if word.eq_ignore_ascii_case("and") => lints.push(Lint::warning(
+            token.span,
+            "AND is not an operator, the terms separated by spaces are already required",
+        )),
+        TokenKind::Word(word) => {
+            // eg: "FROM:user" or "Lang:en"
+            if let Some((name, _)) = word.split_once(':') {
+                let lowercase = name.to_lowercase();
+                if OPERATORS.contains(&lowercase.as_str()) {
+                    lints.push(Lint::error(
+                        token.span,
+                        format!("Operators are lowercase, use {}:", lowercase),
+                    ));
+                }
+            }
+        }
+        TokenKind::Operator { name, value } => {
+            if !OPERATORS.contains(&name.as_str()) {
+                let hint = if name == "http" || name == "https" {
+                    " (put the urls between quotes)"
+                } else {
+                    ""
+                };
+                lints.push(Lint::error(
+                    token.span,
+                    format!("Unknown operator {}:{}", name, hint),
+                ));
+            } else if value.is_empty() {
+                lints.push(Lint::error(
+                    token.span,
+                    format!("The operator {}: needs a value", name),
+                ));
+            } else if access == AccessLevel::Standard && ACADEMIC_OPERATORS.contains(&name.as_str())
+            {
+                lints.push(Lint::error(
+                    token.span,
+                    format!("The operator {}: needs Academic Research access", name),
+                ));
+            } else if let Some(expected) = coordinates_problem(name, value) {
+                lints.push(Lint::error(
+                    token.span,
+                    format!("The operator {}: expects {}", name, expected),
+                ));
+            } else if name == "is" && !IS_VALUES.contains(&value.as_str()) {
+                lints.push(Lint::error(
+                    token.span,
+                    format!(
+                        "Unknown value is:{}, expected one of: {}",
+                        value,
+                        IS_VALUES.join(", ")
+                    ),
+                ));
+            } else if name == "has" && !HAS_VALUES.contains(&value.as_str()) {
+                lints.push(Lint::error(
+                    token.span,
+                    format!(
+                        "Unknown value has:{}, expected one of: {}",
+                        value,
+                        HAS_VALUES.join(", ")
+                    ),
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Format expected by `point_radius:` and `bounding_box:` if the value
+/// doesn't follow it
+fn coordinates_problem(name: &str, value: &str) -> Option<&'static str> {
+    let is_number = |o: &str| matches!(o.parse::<f64>(), Ok(n) if n.is_finite());
+    let numbers = value
+        .strip_prefix('[')
+        .and_then(|o| o.strip_suffix(']'))
+        .map(|o| o.split_whitespace().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let (valid, expected) = match name {
+        "point_radius" => {
+            let valid = match numbers.as_slice() {
+                [longitude, latitude, radius] => {
+                    let radius = radius
+                        .strip_suffix("km")
+                        .or_else(|| radius.strip_suffix("mi"));
+                    is_number(longitude) && is_number(latitude) && radius.is_some_and(is_number)
+                }
+                _ => false,
+            };
+            (
+                valid,
+                "[longitude latitude radius] (eg: [2.355128 48.861118 16km])",
+            )
+        }
+        "bounding_box" => (
+            numbers.len() == 4 && numbers.iter().all(|o| is_number(o)),
+            "[west_long south_lat east_long north_lat] \
+             (eg: [-105.301758 39.964069 -105.178505 40.09455])",
+        ),
+        _ => return None,
+    };
+    (!valid).then_some(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(rule: &str) -> Vec<(usize, usize)> {
+        lint(rule, AccessLevel::Standard)
+            .into_iter()
+            .filter(|o| o.is_error())
+            .map(|o| (o.span.start, o.span.end))
+            .collect()
+    }
+
+    #[test]
+    fn valid_rule() {
+        let rule = "python (machine OR deep) learning -is:retweet lang:en";
+        assert_eq!(lint(rule, AccessLevel::Standard), vec![]);
+    }
+
+    #[test]
+    fn points_at_the_problems() {
+        assert_eq!(errors("cat (dog"), vec![(4, 5)]);
+        assert_eq!(errors("cat dog)"), vec![(7, 8)]);
+        assert_eq!(errors("cat - dog"), vec![(4, 5)]);
+        assert_eq!(errors("cat \"dog"), vec![(4, 8)]);
+        assert_eq!(errors("cat OR"), vec![(4, 6)]);
+        assert_eq!(errors("cat bio:dev"), vec![(4, 11)]);
+        assert_eq!(errors("cat FROM:jack"), vec![(4, 13)]);
+    }
+
+    #[test]
+    fn negations() {
+        assert_eq!(errors("-cat -dog"), vec![(0, 9)]);
+        assert_eq!(errors("-(cat OR dog)"), vec![(0, 13)]);
+        assert_eq!(errors("bird -(cat OR dog)"), vec![]);
+    }
+
+    #[test]
+    fn length_and_access() {
+        let rule = format!("cat {}", "a".repeat(600));
+        assert_eq!(errors(&rule), vec![(512, 604)]);
+        assert!(lint(&rule, AccessLevel::Academic).is_empty());
+        assert!(lint("cat bio:dev", AccessLevel::Academic).is_empty());
+    }
+
+    #[test]
+    fn coordinates() {
+        let errors = |rule: &str| {
+            lint(rule, AccessLevel::Academic)
+                .into_iter()
+                .filter(|o| o.is_error())
+                .map(|o| (o.span.start, o.span.end))
+                .collect::<Vec<_>>()
+        };
+        let rule = "cat point_radius:[2.355128 48.861118 16km] \
+                    OR bounding_box:[-105.301758 39.964069 -105.178505 40.09455]";
+        assert_eq!(lint(rule, AccessLevel::Academic), vec![]);
+        assert_eq!(
+            errors("cat point_radius:[2.355128 48.861118]"),
+            vec![(4, 37)]
+        );
+        assert_eq!(errors("cat point_radius:[2.3 48.8 16]"), vec![(4, 30)]);
+        assert_eq!(errors("cat bounding_box:[a b c d]"), vec![(4, 26)]);
+        assert_eq!(errors("cat point_radius:2.3"), vec![(4, 20)]);
+        // missing the closing bracket
+        assert_eq!(errors("cat point_radius:[2.355128 dog"), vec![(17, 30)]);
+    }
+
+    #[test]
+    fn wrong_case() {
+        let lints = lint("cat or dog", AccessLevel::Standard);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].severity, Severity::Warning);
+        assert_eq!(lints[0].underline("cat or dog"), "cat or dog\n    ^^");
+    }
+}
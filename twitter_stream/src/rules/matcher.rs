@@ -0,0 +1,420 @@
+//! Evaluates rules against stored tweets, to see what a rule would have
+//! matched without collecting the tweets again. It approximates the matching
+//! of twitter with the fields of the tweet: keywords and phrases are matched
+//! on the words of the text (ignoring case), hashtags, mentions and cashtags
+//! on the entities, and the operators on their fields (`from:` and `to:` need
+//! the users of the includes to match usernames). The operators that can't
+//! be evaluated locally (eg: `place:`) are unknown, a rule only matches when
+//! the rest of it is enough to tell (eg: `cat OR place:London` on a tweet with
+//! "cat").
+use super::{
+    query::{Has, Is, Operator, ParseError, Query},
+    Rule, RuleSpec,
+};
+use crate::{
+    stream_tweets::{Entities, EntityTag, RuleMatch, StreamResponse, UserData},
+    RuleId, UserId,
+};
+
+/// Operators that can be evaluated locally
+pub const LOCAL_OPERATORS: &[&str] = &[
+    "from",
+    "to",
+    "url",
+    "lang",
+    "is",
+    "has",
+    "context",
+    "conversation_id",
+];
+
+impl Query {
+    /// Evaluates the query on the tweet, `None` if the result depends on
+    /// operators that can't be evaluated locally (see `unsupported_operators`)
+    pub fn matches(&self, tweet: &StreamResponse) -> Option<bool> {
+        match self {
+            Query::Term(term) => Some(match_term(term, tweet)),
+            Query::Phrase(phrase) => Some(match_words(phrase, &tweet.data.text)),
+            Query::Operator(operator) => match_operator(operator, tweet),
+            Query::And(queries) => combine(queries, tweet, false),
+            Query::Or(queries) => combine(queries, tweet, true),
+            Query::Not(query) => query.matches(tweet).map(|o| !o),
+        }
+    }
+
+    /// Operators of the query that can't be evaluated locally (eg: `place:`)
+    pub fn unsupported_operators(&self) -> Vec<&Operator> {
+        match self {
+            Query::Operator(operator) if !is_supported(operator) => vec![operator],
+            Query::And(queries) | Query::Or(queries) => queries
+                .iter()
+                .flat_map(|o| o.unsupported_operators())
+                .collect(),
+            Query::Not(query) => query.unsupported_operators(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Result of an AND (`decisive` false) or an OR (`decisive` true), a query
+/// with the `decisive` result settles it even if others are unknown
+fn combine(queries: &[Query], tweet: &StreamResponse, decisive: bool) -> Option<bool> {
+    let mut unknown = false;
+    for query in queries {
+        match query.matches(tweet) {
+            Some(result) if result == decisive => return Some(decisive),
+            Some(_) => {}
+            None => unknown = true,
+        }
+    }
+    (!unknown).then_some(!decisive)
+}
+
+fn is_supported(operator: &Operator) -> bool {
+    match operator {
+        Operator::Is(Is::Nullcast) => false,
+        Operator::Is(_) | Operator::Has(_) => true,
+        // values that are not an `Is` or `Has`
+        Operator::Other { name, .. } if name == "is" || name == "has" => false,
+        operator => LOCAL_OPERATORS.contains(&operator.name()),
+    }
+}
+
+/// Rules parsed to tag tweets with the rules they match
+#[derive(Clone, Debug)]
+pub struct RuleMatcher {
+    rules: Vec<(Rule, Query)>,
+}
+
+impl RuleMatcher {
+    pub fn new(rules: Vec<Rule>) -> Result<Self, (Rule, ParseError)> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| match rule.query() {
+                Ok(query) => Ok((rule, query)),
+                Err(err) => Err((rule, err)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Rules without id (eg: from a rules file), they get the ids 1, 2, ...
+    pub fn from_specs(specs: Vec<RuleSpec>) -> Result<Self, (Rule, ParseError)> {
+        let rules = specs
+            .into_iter()
+            .zip(1..)
+            .map(|(spec, id)| Rule {
+                id: RuleId(id),
+                value: Some(spec.value),
+                tag: spec.tag,
+            })
+            .collect();
+        Self::new(rules)
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = (&Rule, &Query)> {
+        self.rules.iter().map(|(rule, query)| (rule, query))
+    }
+
+    /// Simulated `matching_rules` of the tweet, without the rules that
+    /// can't be evaluated locally on it
+    pub fn matching_rules(&self, tweet: &StreamResponse) -> Vec<RuleMatch> {
+        self.rules
+            .iter()
+            .filter(|(_, query)| query.matches(tweet) == Some(true))
+            .map(|(rule, _)| RuleMatch {
+                id: rule.id,
+                tag: rule.tag.clone().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Replaces the `matching_rules` of the tweet, returns false if no rule
+    /// matches
+    pub fn tag(&self, tweet: &mut StreamResponse) -> bool {
+        let matching_rules = self.matching_rules(tweet);
+        let matched = !matching_rules.is_empty();
+        tweet.matching_rules = Some(matching_rules);
+        matched
+    }
+}
+
+fn match_term(term: &str, tweet: &StreamResponse) -> bool {
+    let entities = tweet.data.entities.as_ref();
+    let mut chars = term.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some('#'), tag) if !tag.is_empty() => {
+            match_tag(entities.and_then(|o| o.hashtags.as_ref()), tag, tweet, '#')
+        }
+        (Some('$'), tag) if !tag.is_empty() => {
+            match_tag(entities.and_then(|o| o.cashtags.as_ref()), tag, tweet, '$')
+        }
+        (Some('@'), username) if !username.is_empty() => {
+            match entities.and_then(|o| o.mentions.as_ref()) {
+                Some(mentions) => mentions
+                    .iter()
+                    .any(|o| o.username.eq_ignore_ascii_case(username)),
+                None => text_tags(&tweet.data.text, '@').any(|o| o.eq_ignore_ascii_case(username)),
+            }
+        }
+        _ => match_words(term, &tweet.data.text),
+    }
+}
+
+/// Exact match of a hashtag or cashtag, with the text when the tweet has no
+/// entities
+fn match_tag(
+    tags: Option<&Vec<EntityTag>>,
+    tag: &str,
+    tweet: &StreamResponse,
+    prefix: char,
+) -> bool {
+    match tags {
+        Some(tags) => tags.iter().any(|o| o.tag.eq_ignore_ascii_case(tag)),
+        None => text_tags(&tweet.data.text, prefix).any(|o| o.eq_ignore_ascii_case(tag)),
+    }
+}
+
+/// Tags of the text starting with `prefix` (without it)
+fn text_tags(text: &str, prefix: char) -> impl Iterator<Item = &str> {
+    text.split(move |c: char| !(c.is_alphanumeric() || c == '_' || c == prefix))
+        .filter_map(move |o| o.strip_prefix(prefix))
+        .filter(|o| !o.is_empty())
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|o| !o.is_empty())
+        .map(|o| o.to_lowercase())
+        .collect()
+}
+
+/// The words of `pattern` appear in order in `text`, terms without words
+/// (eg: emojis) are searched on the text
+fn match_words(pattern: &str, text: &str) -> bool {
+    let pattern_words = words(pattern);
+    if pattern_words.is_empty() {
+        return !pattern.is_empty() && text.contains(pattern);
+    }
+    words(text)
+        .windows(pattern_words.len())
+        .any(|o| o == pattern_words.as_slice())
+}
+
+/// `None` for the operators that can't be evaluated locally
+fn match_operator(operator: &Operator, tweet: &StreamResponse) -> Option<bool> {
+    if !is_supported(operator) {
+        return None;
+    }
+    let data = &tweet.data;
+    let entities = data.entities.as_ref();
+    let matched = match operator {
+        Operator::From(user) => match_user(data.author_id, user, tweet),
+        Operator::To(user) => match_user(data.in_reply_to_user_id, user, tweet),
+        Operator::Lang(lang) => data.lang.as_deref() == Some(lang.as_str()),
+        Operator::Is(Is::Retweet) => has_reference(tweet, "retweeted"),
+        Operator::Is(Is::Reply) => has_reference(tweet, "replied_to"),
+        Operator::Is(Is::Quote) => has_reference(tweet, "quoted"),
+        Operator::Is(Is::Verified) => author(tweet).and_then(|o| o.verified) == Some(true),
+        Operator::Has(Has::Hashtags) => has_entity(entities, |o| &o.hashtags),
+        Operator::Has(Has::Cashtags) => has_entity(entities, |o| &o.cashtags),
+        Operator::Has(Has::Links) => has_entity(entities, |o| &o.urls),
+        Operator::Has(Has::Mentions) => has_entity(entities, |o| &o.mentions),
+        Operator::Has(Has::Media) => has_media(tweet, None),
+        Operator::Has(Has::Images) => has_media(tweet, Some(&["photo"])),
+        Operator::Has(Has::Videos) => has_media(tweet, Some(&["video", "animated_gif"])),
+        Operator::Has(Has::Geo) => data.geo.is_some(),
+        Operator::Context(context) => match_context(context, tweet),
+        Operator::Url(url) => {
+            let url = url.to_lowercase();
+            entities
+                .and_then(|o| o.urls.as_ref())
+                .into_iter()
+                .flatten()
+                .flat_map(|o| vec![Some(&o.url), Some(&o.expanded_url), o.unwound_url.as_ref()])
+                .flatten()
+                .any(|o| o.to_lowercase().contains(&url))
+        }
+        Operator::Other { name, value } if name == "conversation_id" => {
+            data.conversation_id.map(|o| o.to_string()).as_deref() == Some(value.as_str())
+        }
+        Operator::Is(Is::Nullcast) | Operator::Other { .. } => return None,
+    };
+    Some(matched)
+}
+
+fn author(tweet: &StreamResponse) -> Option<&UserData> {
+    let author_id = tweet.data.author_id?;
+    tweet.includes.users.iter().find(|o| o.id == author_id)
+}
+
+/// `user` is a username (with or without @) or an id
+fn match_user(id: Option<UserId>, user: &str, tweet: &StreamResponse) -> bool {
+    let id = match id {
+        Some(id) => id,
+        None => return false,
+    };
+    if let Ok(user_id) = user.parse::<UserId>() {
+        return id == user_id;
+    }
+    let username = user.trim_start_matches('@');
+    tweet
+        .includes
+        .users
+        .iter()
+        .any(|o| o.id == id && o.username.eq_ignore_ascii_case(username))
+}
+
+fn has_reference(tweet: &StreamResponse, reference_type: &str) -> bool {
+    tweet
+        .data
+        .referenced_tweets
+        .iter()
+        .flatten()
+        .any(|o| o.reference_type == reference_type)
+}
+
+fn has_entity<T, F>(entities: Option<&Entities>, field: F) -> bool
+where
+    F: Fn(&Entities) -> &Option<Vec<T>>,
+{
+    entities
+        .and_then(|o| field(o).as_ref())
+        .map(|o| !o.is_empty())
+        .unwrap_or(false)
+}
+
+/// Media of the given types (any media with `None`), the types are read from
+/// the media of the includes
+fn has_media(tweet: &StreamResponse, media_types: Option<&[&str]>) -> bool {
+    let media_keys = tweet
+        .data
+        .attachments
+        .as_ref()
+        .and_then(|o| o.media_keys.as_ref());
+    match (media_keys, media_types) {
+        (Some(keys), None) => !keys.is_empty(),
+        (Some(keys), Some(media_types)) => {
+            tweet.includes.media.iter().any(|o| {
+                keys.contains(&o.media_key) && media_types.contains(&o.media_type.as_str())
+            })
+        }
+        (None, _) => false,
+    }
+}
+
+/// `domain_id.entity_id` or `domain_id.*`
+fn match_context(context: &str, tweet: &StreamResponse) -> bool {
+    let (domain, entity) = context.split_once('.').unwrap_or((context, "*"));
+    tweet
+        .data
+        .context_annotations
+        .iter()
+        .flatten()
+        .any(|o| o.domain.id == domain && (entity == "*" || o.entity.id == entity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tweet(data: serde_json::Value, includes: serde_json::Value) -> StreamResponse {
+        serde_json::from_value(json!({ "data": data, "includes": includes })).unwrap()
+    }
+
+    fn matches(rule: &str, tweet: &StreamResponse) -> bool {
+        rule.parse::<Query>().unwrap().matches(tweet) == Some(true)
+    }
+
+    #[test]
+    fn keywords_and_phrases() {
+        let tweet = tweet(
+            json!({"id": "1", "text": "Learning Python for Machine-Learning 🎉 #Rust", "lang": "en"}),
+            json!({}),
+        );
+        assert!(matches("python (machine OR deep) learning lang:en", &tweet));
+        assert!(matches("\"machine learning\" 🎉", &tweet));
+        assert!(matches("#rust", &tweet));
+        assert!(!matches("\"learning machine\"", &tweet));
+        assert!(!matches("pyth", &tweet));
+        assert!(!matches("python -#rust", &tweet));
+        assert!(!matches("python lang:es", &tweet));
+    }
+
+    #[test]
+    fn operators() {
+        let tweet = tweet(
+            json!({
+                "id": "1",
+                "text": "RT @jack: hello",
+                "author_id": "10",
+                "referenced_tweets": [{"id": "2", "type": "retweeted"}],
+                "entities": {
+                    "mentions": [{"start": 3, "end": 8, "username": "jack"}],
+                    "urls": [{"start": 0, "end": 1, "url": "https://t.co/x",
+                              "expanded_url": "https://developer.twitter.com/en",
+                              "display_url": "developer.twitter.com"}]
+                },
+                "context_annotations": [{"domain": {"id": "10"}, "entity": {"id": "99"}}]
+            }),
+            json!({"users": [{"id": "10", "name": "Dev", "username": "TwitterDev"}]}),
+        );
+        assert!(matches("from:twitterdev is:retweet", &tweet));
+        assert!(matches("from:10 @jack has:mentions has:links", &tweet));
+        assert!(matches(
+            "url:\"developer.twitter.com\" context:10.*",
+            &tweet
+        ));
+        assert!(!matches("hello -is:retweet", &tweet));
+        assert!(!matches(
+            "hello (is:reply OR is:quote OR has:hashtags)",
+            &tweet
+        ));
+        assert!(!matches("hello place:London", &tweet));
+    }
+
+    #[test]
+    fn unsupported_operators_are_unknown() {
+        let tweet = tweet(json!({"id": "1", "text": "my cat"}), json!({}));
+        let query = |rule: &str| rule.parse::<Query>().unwrap().matches(&tweet);
+        assert_eq!(query("cat -place:London"), None);
+        assert_eq!(query("cat -(bio:dev OR is:nullcast)"), None);
+        assert_eq!(query("dog -place:London"), Some(false));
+        assert_eq!(query("cat OR place:London"), Some(true));
+        assert_eq!(query("-cat -place:London"), Some(false));
+
+        let rules = vec![RuleSpec {
+            value: "cat -bio:dev".into(),
+            tag: None,
+        }];
+        let matcher = RuleMatcher::from_specs(rules).unwrap();
+        assert!(matcher.matching_rules(&tweet).is_empty());
+    }
+
+    #[test]
+    fn tags_tweets() {
+        let rules = vec![
+            RuleSpec {
+                value: "hello".into(),
+                tag: Some("greetings".into()),
+            },
+            RuleSpec {
+                value: "bye".into(),
+                tag: None,
+            },
+        ];
+        let matcher = RuleMatcher::from_specs(rules).unwrap();
+        let mut tweet = tweet(json!({"id": "1", "text": "hello world"}), json!({}));
+        assert!(matcher.tag(&mut tweet));
+        let matching_rules = tweet.matching_rules.unwrap();
+        assert_eq!(matching_rules.len(), 1);
+        assert_eq!(matching_rules[0].id, RuleId(1));
+        assert_eq!(matching_rules[0].tag, "greetings");
+
+        let query = "hello place:London bio:dev".parse::<Query>().unwrap();
+        let unsupported = query.unsupported_operators();
+        assert_eq!(unsupported.len(), 2);
+        assert_eq!(unsupported[1].name(), "bio");
+    }
+}
@@ -1,19 +1,29 @@
 pub mod create;
 pub mod delete;
 pub mod get;
+pub mod lexer;
+pub mod lint;
+pub mod matcher;
+pub mod query;
+pub mod sync;
 
-pub use create::create_rule;
-pub use delete::{delete_rule, delete_rules};
+pub use create::{create_rule, AddRulesRequest, CreateRuleError, CreateRulesResult};
+pub use delete::{delete_rule, delete_rules, DeleteRulesRequest};
 pub use get::get_rules;
+pub use lint::{lint, AccessLevel, Lint};
+pub use matcher::RuleMatcher;
+pub use query::Query;
+pub use sync::{RuleSpec, RulesFile, SyncPlan, SyncSummary};
 
+use crate::RuleId;
 use serde::Deserialize;
 use std::collections::HashMap;
 
-pub const RULES_URL: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+pub const RULES_PATH: &str = "/2/tweets/search/stream/rules";
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Rule {
-    pub id: String,
+    pub id: RuleId,
     pub value: Option<String>,
     pub tag: Option<String>,
 }
@@ -31,5 +41,7 @@ impl std::fmt::Display for Rule {
 
 #[derive(Debug, Deserialize)]
 pub struct ResponseRuleMeta {
+    /// Missing in the responses with an error of the whole request
+    #[serde(default)]
     pub summary: HashMap<String, usize>,
 }
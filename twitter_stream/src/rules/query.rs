@@ -0,0 +1,583 @@
+//! Typed rules, they can be built in rust and rendered as the value of a rule
+//! or parsed from the value of an existing rule, eg:
+//! ```
+//! use twitter_stream::rules::query::{is, lang, term, Is, Query};
+//!
+//! let query = term("python")
+//!     & (term("machine") | term("deep"))
+//!     & term("learning")
+//!     & !is(Is::Retweet)
+//!     & lang("en");
+//! let rule = "python (machine OR deep) learning -is:retweet lang:en";
+//! assert_eq!(query.to_string(), rule);
+//! assert_eq!(rule.parse::<Query>().unwrap(), query);
+//! ```
+use super::{
+    lexer::{tokenize, LexError, Span, Token, TokenKind},
+    Rule,
+};
+use std::{fmt, ops, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    /// Keyword, emoji, #hashtag, @mention or $cashtag
+    Term(String),
+    /// "Exact phrase"
+    Phrase(String),
+    Operator(Operator),
+    /// Terms separated by spaces, all of them must match
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operator {
+    /// Tweets of a user (username or id)
+    From(String),
+    /// Replies to a user (username or id)
+    To(String),
+    /// Language (BCP 47 code, eg: "en")
+    Lang(String),
+    Is(Is),
+    Has(Has),
+    /// Context annotation, "domain_id.entity_id" (eg: "10.799022225751871488")
+    Context(String),
+    Url(String),
+    /// Any other operator (eg: `entity:`, `place:`)
+    Other {
+        name: String,
+        value: String,
+    },
+}
+
+macro_rules! filter_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value),*
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err(format!("Invalid value {:?}", s)),
+                }
+            }
+        }
+    };
+}
+
+filter_enum!(
+    /// Values of the `is:` operator
+    Is {
+        Retweet => "retweet",
+        Reply => "reply",
+        Quote => "quote",
+        Verified => "verified",
+        Nullcast => "nullcast",
+    }
+);
+
+filter_enum!(
+    /// Values of the `has:` operator
+    Has {
+        Hashtags => "hashtags",
+        Cashtags => "cashtags",
+        Links => "links",
+        Mentions => "mentions",
+        Media => "media",
+        Images => "images",
+        Videos => "videos",
+        Geo => "geo",
+    }
+);
+
+impl Operator {
+    pub fn new(name: &str, value: &str) -> Self {
+        let other = || Operator::Other {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+        match name {
+            "from" => Operator::From(value.to_string()),
+            "to" => Operator::To(value.to_string()),
+            "lang" => Operator::Lang(value.to_string()),
+            "is" => value.parse().map(Operator::Is).unwrap_or_else(|_| other()),
+            "has" => value.parse().map(Operator::Has).unwrap_or_else(|_| other()),
+            "context" => Operator::Context(value.to_string()),
+            "url" => Operator::Url(value.to_string()),
+            _ => other(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Operator::From(_) => "from",
+            Operator::To(_) => "to",
+            Operator::Lang(_) => "lang",
+            Operator::Is(_) => "is",
+            Operator::Has(_) => "has",
+            Operator::Context(_) => "context",
+            Operator::Url(_) => "url",
+            Operator::Other { name, .. } => name,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            Operator::From(value)
+            | Operator::To(value)
+            | Operator::Lang(value)
+            | Operator::Context(value)
+            | Operator::Url(value)
+            | Operator::Other { value, .. } => value,
+            Operator::Is(value) => value.as_str(),
+            Operator::Has(value) => value.as_str(),
+        }
+    }
+}
+
+/// Texts that can't be read as a single word (eg: "new york", "OR" or
+/// "-cat") are phrases
+pub fn term<T: Into<String>>(term: T) -> Query {
+    let term = term.into();
+    if is_word(&term) {
+        Query::Term(term)
+    } else {
+        Query::Phrase(term)
+    }
+}
+
+pub fn phrase<T: Into<String>>(phrase: T) -> Query {
+    Query::Phrase(phrase.into())
+}
+
+pub fn from<T: Into<String>>(user: T) -> Query {
+    Query::Operator(Operator::From(user.into()))
+}
+
+pub fn to<T: Into<String>>(user: T) -> Query {
+    Query::Operator(Operator::To(user.into()))
+}
+
+pub fn lang<T: Into<String>>(lang: T) -> Query {
+    Query::Operator(Operator::Lang(lang.into()))
+}
+
+pub fn is(value: Is) -> Query {
+    Query::Operator(Operator::Is(value))
+}
+
+pub fn has(value: Has) -> Query {
+    Query::Operator(Operator::Has(value))
+}
+
+pub fn context<T: Into<String>>(context: T) -> Query {
+    Query::Operator(Operator::Context(context.into()))
+}
+
+pub fn url<T: Into<String>>(url: T) -> Query {
+    Query::Operator(Operator::Url(url.into()))
+}
+
+/// All the queries must match, `None` without queries (an empty rule is
+/// not valid)
+pub fn all<I: IntoIterator<Item = Query>>(queries: I) -> Option<Query> {
+    let queries = queries.into_iter().collect::<Vec<_>>();
+    (!queries.is_empty()).then(|| group(queries, Query::And))
+}
+
+/// Any of the queries must match, `None` without queries (an empty rule is
+/// not valid)
+pub fn any<I: IntoIterator<Item = Query>>(queries: I) -> Option<Query> {
+    let queries = queries.into_iter().collect::<Vec<_>>();
+    (!queries.is_empty()).then(|| group(queries, Query::Or))
+}
+
+/// Expects at least one query
+fn group(mut queries: Vec<Query>, build: fn(Vec<Query>) -> Query) -> Query {
+    match queries.len() {
+        1 => queries.remove(0),
+        _ => build(queries),
+    }
+}
+
+impl ops::BitAnd for Query {
+    type Output = Query;
+
+    fn bitand(self, rhs: Query) -> Query {
+        match self {
+            Query::And(mut queries) => {
+                queries.push(rhs);
+                Query::And(queries)
+            }
+            query => Query::And(vec![query, rhs]),
+        }
+    }
+}
+
+impl ops::BitOr for Query {
+    type Output = Query;
+
+    fn bitor(self, rhs: Query) -> Query {
+        match self {
+            Query::Or(mut queries) => {
+                queries.push(rhs);
+                Query::Or(queries)
+            }
+            query => Query::Or(vec![query, rhs]),
+        }
+    }
+}
+
+impl ops::Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // only terms built without `term` can need quotes
+            Query::Term(term) if !is_word(term) => write_quoted(f, term),
+            Query::Term(term) => write!(f, "{}", term),
+            Query::Phrase(phrase) => write_quoted(f, phrase),
+            Query::Operator(operator) => write!(f, "{}", operator),
+            Query::And(queries) => write_group(f, queries, " "),
+            Query::Or(queries) => write_group(f, queries, " OR "),
+            Query::Not(query) => match **query {
+                Query::And(_) | Query::Or(_) => write!(f, "-({})", query),
+                _ => write!(f, "-{}", query),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name())?;
+        let value = self.value();
+        // twitter asks to quote the urls, any value with a colon is quoted.
+        // Coordinates between brackets are read whole and can't be quoted
+        let tokens = tokenize(&format!("{}:{}", self.name(), value));
+        let same = matches!(
+            tokens.as_deref(),
+            Ok([Token { kind: TokenKind::Operator { value: read, .. }, .. }]) if read == value
+        );
+        if value.is_empty() || value.contains(':') || !same {
+            write_quoted(f, value)
+        } else {
+            write!(f, "{}", value)
+        }
+    }
+}
+
+/// Nested groups are always between parentheses (AND is evaluated before
+/// OR, but they make the rule clearer and keep its structure when parsed)
+fn write_group(f: &mut fmt::Formatter<'_>, queries: &[Query], separator: &str) -> fmt::Result {
+    for (i, query) in queries.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        match query {
+            Query::And(_) | Query::Or(_) => write!(f, "({})", query)?,
+            _ => write!(f, "{}", query)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_quoted(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"");
+    write!(f, "\"{}\"", escaped)
+}
+
+/// The lexer reads the text as this word (eg: not "OR", "-cat" or "new york")
+fn is_word(text: &str) -> bool {
+    matches!(
+        tokenize(text).as_deref(),
+        Ok([Token { kind: TokenKind::Word(word), .. }]) if word == text
+    )
+}
+
+/// Problem found while parsing a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at {}..{})",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> Self {
+        Self {
+            span: err.span,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+/// Parses the value of a rule, AND (spaces) is evaluated before OR
+pub fn parse(rule: &str) -> Result<Query, ParseError> {
+    let tokens = tokenize(rule)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        end: Span::new(rule.len(), rule.len()),
+    };
+    let query = parser.or()?;
+    match parser.peek() {
+        Some(token) => Err(parser.error(token.span, "Unmatched closing parenthesis")),
+        None => Ok(query),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    /// Span for the errors at the end of the rule
+    end: Span,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, span: Span, message: &str) -> ParseError {
+        ParseError {
+            span,
+            message: message.to_string(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.and()?];
+        while let Some(Token {
+            kind: TokenKind::Or,
+            ..
+        }) = self.peek()
+        {
+            self.next();
+            queries.push(self.and()?);
+        }
+        Ok(group(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut queries = Vec::new();
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Or | TokenKind::RParen => break,
+                _ => queries.push(self.unary()?),
+            }
+        }
+        if queries.is_empty() {
+            let span = self.peek().map(|o| o.span).unwrap_or(self.end);
+            return Err(self.error(span, "Expected a term"));
+        }
+        Ok(group(queries, Query::And))
+    }
+
+    fn unary(&mut self) -> Result<Query, ParseError> {
+        let token = self.next().expect("called with tokens left");
+        let query = match &token.kind {
+            TokenKind::Word(word) => Query::Term(word.clone()),
+            TokenKind::Phrase(phrase) => Query::Phrase(phrase.clone()),
+            TokenKind::Operator { name, value } => Query::Operator(Operator::new(name, value)),
+            TokenKind::Negation => match self.peek() {
+                Some(next) if next.span.start == token.span.end => match next.kind {
+                    TokenKind::Or | TokenKind::RParen => {
+                        return Err(self.error(token.span, "Bare negation"))
+                    }
+                    _ => Query::Not(Box::new(self.unary()?)),
+                },
+                _ => return Err(self.error(token.span, "Bare negation")),
+            },
+            TokenKind::LParen => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => query,
+                    _ => return Err(self.error(token.span, "Unclosed parenthesis")),
+                }
+            }
+            TokenKind::Or | TokenKind::RParen => {
+                return Err(self.error(token.span, "Expected a term"))
+            }
+        };
+        Ok(query)
+    }
+}
+
+impl Rule {
+    /// Parses the value of the rule
+    pub fn query(&self) -> Result<Query, ParseError> {
+        parse(self.value.as_deref().unwrap_or(""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(rule: &str) -> Query {
+        let query = parse(rule).unwrap();
+        assert_eq!(query.to_string(), rule);
+        assert_eq!(parse(&query.to_string()).unwrap(), query);
+        query
+    }
+
+    #[test]
+    fn readme_rule() {
+        let query = roundtrip("python (machine OR deep) learning -is:retweet lang:en");
+        assert_eq!(
+            query,
+            Query::And(vec![
+                term("python"),
+                Query::Or(vec![term("machine"), term("deep")]),
+                term("learning"),
+                Query::Not(Box::new(is(Is::Retweet))),
+                lang("en"),
+            ])
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let query = roundtrip(
+            "from:TwitterDev to:jack has:media context:10.799022225751871488 \
+             url:\"https://developer.twitter.com\" place:\"new york city\" \
+             point_radius:[2.355128 48.861118 16km] \
+             bounding_box:[-105.301758 39.964069 -105.178505 40.09455]",
+        );
+        match query {
+            Query::And(queries) => {
+                assert_eq!(queries[0], from("TwitterDev"));
+                assert_eq!(queries[2], has(Has::Media));
+                assert_eq!(
+                    queries[5],
+                    Query::Operator(Operator::Other {
+                        name: "place".into(),
+                        value: "new york city".into()
+                    })
+                );
+                assert_eq!(
+                    queries[7],
+                    Query::Operator(Operator::Other {
+                        name: "bounding_box".into(),
+                        value: "[-105.301758 39.964069 -105.178505 40.09455]".into()
+                    })
+                );
+                assert_eq!(queries.len(), 8);
+            }
+            _ => panic!("Expected an AND"),
+        }
+    }
+
+    #[test]
+    fn quoting() {
+        roundtrip(r#"("happy birthday" OR "say \"hi\"") #rust @TwitterDev 🎉"#);
+        roundtrip("-(cat OR dog) bird");
+        roundtrip("meeting 10:30 C++ ❤️");
+        let query = term("a b") & term("OR") & term("-x") & term("from:jack") & from("a b");
+        assert_eq!(
+            query.to_string(),
+            r#""a b" "OR" "-x" "from:jack" from:"a b""#
+        );
+        assert_eq!(parse(&query.to_string()).unwrap(), query);
+        assert_eq!(term("10:30"), Query::Term("10:30".into()));
+        assert_eq!(term("new york"), phrase("new york"));
+    }
+
+    #[test]
+    fn precedence_and_groups() {
+        assert_eq!(
+            parse("a OR b c").unwrap(),
+            Query::Or(vec![term("a"), term("b") & term("c")])
+        );
+        assert_eq!(parse("a OR b c").unwrap().to_string(), "a OR (b c)");
+        assert_eq!(parse("((a))").unwrap(), term("a"));
+        roundtrip("a (b c) (d OR (e OR f))");
+    }
+
+    #[test]
+    fn builder_roundtrip() {
+        let queries = vec![
+            all(vec![
+                term("cat"),
+                !has(Has::Images),
+                any(vec![lang("en"), lang("es")]).unwrap(),
+            ])
+            .unwrap(),
+            !(term("a") | phrase("b c")) & is(Is::Verified),
+            context("66.857879456773357569") & !url("https://t.co/x"),
+        ];
+        for query in queries {
+            assert_eq!(parse(&query.to_string()).unwrap(), query);
+        }
+        let point = Operator::new("point_radius", "[2.355128 48.861118 16km]");
+        assert_eq!(point.to_string(), "point_radius:[2.355128 48.861118 16km]");
+    }
+
+    #[test]
+    fn empty_groups() {
+        assert_eq!(all(vec![]), None);
+        assert_eq!(any(vec![]), None);
+        assert_eq!(any(vec![term("cat")]), Some(term("cat")));
+    }
+
+    #[test]
+    fn errors() {
+        let span = |rule: &str| parse(rule).unwrap_err().span;
+        assert_eq!(span("cat (dog"), Span::new(4, 5));
+        assert_eq!(span("cat dog)"), Span::new(7, 8));
+        assert_eq!(span("cat - dog"), Span::new(4, 5));
+        assert_eq!(span("cat OR"), Span::new(6, 6));
+        assert_eq!(span("\"cat"), Span::new(0, 4));
+        assert_eq!(span("cat point_radius:[2.3 48.8 cat"), Span::new(17, 30));
+    }
+}
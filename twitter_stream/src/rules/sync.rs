@@ -0,0 +1,212 @@
+use super::{CreateRuleError, Rule};
+use crate::TwitterClient;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path};
+
+/// Rule as written on a rules file, it has no id
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RuleSpec {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl RuleSpec {
+    pub fn matches(&self, rule: &Rule) -> bool {
+        rule.value.as_deref() == Some(self.value.as_str()) && rule.tag == self.tag
+    }
+}
+
+impl std::fmt::Display for RuleSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.value)?;
+        match &self.tag {
+            Some(tag) => write!(f, " [tag: {:?}]", tag),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Desired rules of the stream, eg (TOML):
+/// ```toml
+/// [[rules]]
+/// value = "cat has:media"
+/// tag = "cats with media"
+/// ```
+/// or (YAML):
+/// ```yaml
+/// rules:
+///   - value: cat has:media
+///     tag: cats with media
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RulesFile {
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+}
+
+impl RulesFile {
+    /// Reads a `.toml`, `.yaml` or `.yml` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let txt =
+            std::fs::read_to_string(path).with_context(|| format!("Couldn't read {:?}", path))?;
+        let extension = path.extension().and_then(|o| o.to_str()).unwrap_or("");
+        let file = match extension {
+            "toml" => toml::from_str(&txt).map_err(anyhow::Error::from),
+            "yaml" | "yml" => serde_yaml::from_str(&txt).map_err(anyhow::Error::from),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown rules file format {:?}, use .toml or .yaml",
+                    path
+                ))
+            }
+        };
+        file.with_context(|| format!("Couldn't parse {:?}", path))
+    }
+}
+
+/// Changes needed to go from the current rules to the desired ones, rules
+/// are matched by value and tag (changing the tag of a rule replaces it)
+#[derive(Clone, Debug, Default)]
+pub struct SyncPlan {
+    pub add: Vec<RuleSpec>,
+    pub delete: Vec<Rule>,
+    pub unchanged: Vec<Rule>,
+}
+
+impl SyncPlan {
+    pub fn new(desired: &[RuleSpec], current: &[Rule]) -> Self {
+        let mut plan = SyncPlan::default();
+        let mut seen = HashSet::new();
+        for spec in desired {
+            if seen.insert(spec) && !current.iter().any(|rule| spec.matches(rule)) {
+                plan.add.push(spec.clone());
+            }
+        }
+        for rule in current {
+            if desired.iter().any(|spec| spec.matches(rule)) {
+                plan.unchanged.push(rule.clone());
+            } else {
+                plan.delete.push(rule.clone());
+            }
+        }
+        plan
+    }
+
+    /// There is nothing to add or delete
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// Result of `TwitterClient::apply_rules`
+#[derive(Clone, Debug, Default)]
+pub struct SyncSummary {
+    pub created: Vec<Rule>,
+    /// Rules of the plan that couldn't be created
+    pub errors: Vec<CreateRuleError>,
+    pub deleted: usize,
+}
+
+impl TwitterClient {
+    /// Compares the desired rules with the rules of the stream
+    pub async fn plan_rules(&self, desired: &[RuleSpec]) -> Result<SyncPlan> {
+        let current = self.rules().await?.data.unwrap_or_default();
+        Ok(SyncPlan::new(desired, &current))
+    }
+
+    /// Applies the plan creating the new rules first, if some of them can't
+    /// be created nothing is deleted. Then the old rules are deleted and the
+    /// rules that only change their tag are added again (a value can't be
+    /// added twice)
+    pub async fn apply_rules(&self, plan: &SyncPlan) -> Result<SyncSummary> {
+        let (retagged, new): (Vec<_>, Vec<_>) = plan.add.iter().cloned().partition(|spec| {
+            plan.delete
+                .iter()
+                .any(|rule| rule.value.as_deref() == Some(spec.value.as_str()))
+        });
+
+        let mut summary = SyncSummary::default();
+        if !new.is_empty() {
+            let res = self.create_rules(new).await?;
+            summary.created = res.created;
+            summary.errors = res.errors;
+            if !summary.errors.is_empty() {
+                return Ok(summary);
+            }
+        }
+        if !plan.delete.is_empty() {
+            let ids = plan.delete.iter().map(|o| o.id).collect();
+            summary.deleted = self.delete_rules(ids).await?;
+        }
+        if !retagged.is_empty() {
+            let res = self.create_rules(retagged).await?;
+            summary.created.extend(res.created);
+            summary.errors.extend(res.errors);
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuleId;
+
+    fn spec(value: &str, tag: Option<&str>) -> RuleSpec {
+        RuleSpec {
+            value: value.into(),
+            tag: tag.map(String::from),
+        }
+    }
+
+    fn rule(id: u64, value: &str, tag: Option<&str>) -> Rule {
+        Rule {
+            id: RuleId(id),
+            value: Some(value.into()),
+            tag: tag.map(String::from),
+        }
+    }
+
+    fn ids(rules: &[Rule]) -> Vec<u64> {
+        rules.iter().map(|o| o.id.as_u64()).collect()
+    }
+
+    #[test]
+    fn plan() {
+        let desired = vec![spec("cat", Some("cats")), spec("dog", None)];
+        let current = vec![rule(1, "cat", Some("cats")), rule(2, "bird", None)];
+        let plan = SyncPlan::new(&desired, &current);
+        assert_eq!(plan.add, vec![spec("dog", None)]);
+        assert_eq!(ids(&plan.delete), vec![2]);
+        assert_eq!(ids(&plan.unchanged), vec![1]);
+        assert!(!plan.is_empty());
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let desired = vec![spec("cat", None)];
+        let plan = SyncPlan::new(&desired, &[rule(1, "cat", None)]);
+        assert!(plan.is_empty());
+        assert_eq!(ids(&plan.unchanged), vec![1]);
+    }
+
+    #[test]
+    fn duplicated_specs_are_added_once() {
+        let desired = vec![spec("cat", None), spec("cat", None), spec("dog", None)];
+        let plan = SyncPlan::new(&desired, &[]);
+        assert_eq!(plan.add, vec![spec("cat", None), spec("dog", None)]);
+    }
+
+    #[test]
+    fn tag_change_replaces_the_rule() {
+        let desired = vec![spec("cat", Some("new"))];
+        let current = vec![rule(1, "cat", Some("old")), rule(2, "dog", None)];
+        let plan = SyncPlan::new(&desired, &current);
+        assert_eq!(plan.add, desired);
+        assert_eq!(ids(&plan.delete), vec![1, 2]);
+        assert!(plan.unchanged.is_empty());
+    }
+}
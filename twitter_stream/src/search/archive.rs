@@ -0,0 +1,288 @@
+use super::SearchQuery;
+use crate::{
+    stream_tweets::{ResponseMeta, StreamResponse},
+    TweetId, TwitterClient,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+pub const SEARCH_ALL_PATH: &str = "/2/tweets/search/all";
+
+/// Maximum number of results per request of the full-archive search
+pub const MAX_ARCHIVE_RESULTS: usize = 500;
+
+/// The full-archive search allows only one request per second
+pub const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Progress of a full-archive search, saved after each page so an interrupted
+/// search can be resumed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub query: SearchQuery,
+    /// Token of the next page, `None` before the first request
+    pub next_token: Option<String>,
+    pub pages: usize,
+    pub tweets: usize,
+    pub newest_id: Option<TweetId>,
+    pub oldest_id: Option<TweetId>,
+    /// There are no more pages
+    pub finished: bool,
+    /// Tweets of the next page that were already saved (the page was cut
+    /// short), they are skipped when resuming
+    #[serde(default)]
+    pub skip: usize,
+}
+
+impl Checkpoint {
+    pub fn new(query: SearchQuery) -> Self {
+        Self {
+            query,
+            next_token: None,
+            pages: 0,
+            tweets: 0,
+            newest_id: None,
+            oldest_id: None,
+            finished: false,
+            skip: 0,
+        }
+    }
+
+    /// Returns `None` if the file doesn't exist
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let txt = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read checkpoint {:?}", path))?;
+        let checkpoint = serde_json::from_str(&txt)
+            .with_context(|| format!("Couldn't parse checkpoint {:?}", path))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Counts a page of `page_len` tweets, returns how many tweets at the
+    /// start of the page were already saved (they must be skipped)
+    pub fn advance(&mut self, meta: &ResponseMeta, page_len: usize) -> usize {
+        let skip = self.skip.min(page_len);
+        self.skip = 0;
+        self.pages += 1;
+        self.tweets += page_len - skip;
+        self.next_token = meta.next_token.clone();
+        self.finished = self.next_token.is_none();
+        if self.newest_id.is_none() {
+            self.newest_id = meta.newest_id;
+        }
+        if meta.oldest_id.is_some() {
+            self.oldest_id = meta.oldest_id;
+        }
+        skip
+    }
+
+    /// Checkpoint made before a page (`self`) for the case where only the
+    /// first `saved` tweets of the page were processed (after the skipped
+    /// ones), resuming requests the page again
+    pub fn partial(&self, saved: usize) -> Self {
+        let mut checkpoint = self.clone();
+        checkpoint.tweets += saved;
+        checkpoint.skip += saved;
+        checkpoint
+    }
+
+    /// Writes to a temporary file first so an interruption doesn't leave
+    /// a corrupted checkpoint
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path).with_context(|| format!("Couldn't save checkpoint {:?}", path))
+    }
+}
+
+/// Full-archive search (Academic Research access only), it's read page by page
+/// waiting between requests to respect the rate limits (the rate limited
+/// requests are retried by the client).
+///
+/// The checkpoint is updated on memory with every page, call `save_checkpoint`
+/// once the page was processed (or `save_partial_checkpoint` if only some of
+/// its tweets were).
+pub struct ArchiveSearch {
+    client: TwitterClient,
+    checkpoint: Checkpoint,
+    /// Checkpoint before the last page
+    previous: Checkpoint,
+    checkpoint_path: Option<PathBuf>,
+    last_request: Option<Instant>,
+}
+
+impl ArchiveSearch {
+    pub fn new(client: TwitterClient, query: SearchQuery) -> Self {
+        let checkpoint = Checkpoint::new(query);
+        Self {
+            client,
+            previous: checkpoint.clone(),
+            checkpoint,
+            checkpoint_path: None,
+            last_request: None,
+        }
+    }
+
+    /// Resumes from the checkpoint saved on `path` (if it exists), it fails
+    /// if the checkpoint was made for a different query
+    pub fn with_checkpoint<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
+        let path = path.into();
+        if let Some(checkpoint) = Checkpoint::load(&path)? {
+            if checkpoint.query != self.checkpoint.query {
+                return Err(anyhow!(
+                    "The checkpoint {:?} belongs to a different search: {:?}",
+                    path,
+                    checkpoint.query
+                ));
+            }
+            self.previous = checkpoint.clone();
+            self.checkpoint = checkpoint;
+        }
+        self.checkpoint_path = Some(path);
+        Ok(self)
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    pub fn save_checkpoint(&self) -> Result<()> {
+        match &self.checkpoint_path {
+            Some(path) => self.checkpoint.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Saves the checkpoint for the case where only the first `saved` tweets
+    /// of the last page were processed, resuming requests the page again and
+    /// skips them
+    pub fn save_partial_checkpoint(&self, saved: usize) -> Result<()> {
+        let path = match &self.checkpoint_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.previous.partial(saved).save(path)
+    }
+
+    /// Next page of results, `None` when the search is finished
+    pub async fn next_page(&mut self) -> Result<Option<Vec<StreamResponse>>> {
+        if self.checkpoint.finished {
+            return Ok(None);
+        }
+
+        self.wait().await;
+        self.last_request = Some(Instant::now());
+        let (_, res) = self
+            .client
+            .search_page(
+                SEARCH_ALL_PATH,
+                &self.checkpoint.query,
+                self.checkpoint.next_token.as_deref(),
+            )
+            .await?;
+
+        self.previous = self.checkpoint.clone();
+        let skip = self.checkpoint.advance(&res.meta, res.data.len());
+        let mut tweets = res.into_stream_responses();
+        tweets.drain(..skip);
+        Ok(Some(tweets))
+    }
+
+    /// Waits for the request interval
+    async fn wait(&self) {
+        let interval = self
+            .last_request
+            .map(|o| {
+                MIN_REQUEST_INTERVAL
+                    .checked_sub(o.elapsed())
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        if interval > Duration::from_secs(0) {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl TwitterClient {
+    /// Full-archive search (Academic Research access only)
+    pub fn search_all(&self, query: SearchQuery) -> ArchiveSearch {
+        ArchiveSearch::new(self.clone(), query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(next_token: Option<&str>, newest_id: u64, oldest_id: u64) -> ResponseMeta {
+        ResponseMeta {
+            result_count: None,
+            newest_id: Some(TweetId(newest_id)),
+            oldest_id: Some(TweetId(oldest_id)),
+            next_token: next_token.map(String::from),
+        }
+    }
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint::new(SearchQuery::new("cat"))
+    }
+
+    #[test]
+    fn full_pages() {
+        let mut checkpoint = checkpoint();
+        assert_eq!(checkpoint.advance(&meta(Some("a"), 20, 16), 5), 0);
+        assert_eq!(checkpoint.advance(&meta(None, 15, 11), 5), 0);
+        assert_eq!(checkpoint.pages, 2);
+        assert_eq!(checkpoint.tweets, 10);
+        assert_eq!(checkpoint.newest_id, Some(TweetId(20)));
+        assert_eq!(checkpoint.oldest_id, Some(TweetId(11)));
+        assert!(checkpoint.finished);
+    }
+
+    #[test]
+    fn page_cut_and_resumed() {
+        let mut checkpoint = checkpoint();
+        checkpoint.advance(&meta(Some("a"), 20, 16), 5);
+        let previous = checkpoint.clone();
+        checkpoint.advance(&meta(Some("b"), 15, 11), 5);
+        // --limit 7 saves 2 tweets of the second page
+        let mut resumed = previous.partial(2);
+        assert_eq!((resumed.tweets, resumed.skip), (7, 2));
+        assert_eq!(resumed.next_token.as_deref(), Some("a"));
+
+        // the page is requested again and the saved tweets are skipped
+        assert_eq!(resumed.advance(&meta(Some("b"), 15, 11), 5), 2);
+        assert_eq!(resumed.tweets, 10);
+        assert_eq!(resumed.skip, 0);
+        assert_eq!(resumed, checkpoint);
+    }
+
+    #[test]
+    fn page_cut_twice() {
+        let page = meta(None, 20, 16);
+        // --limit 2 on the first page
+        let first = checkpoint().partial(2);
+        let mut resumed = first.clone();
+        assert_eq!(resumed.advance(&page, 5), 2);
+        // --limit 1 on the rest of the page
+        let second = first.partial(1);
+        assert_eq!((second.tweets, second.skip), (3, 3));
+
+        let mut resumed = second.clone();
+        assert_eq!(resumed.advance(&page, 5), 3);
+        assert_eq!((resumed.tweets, resumed.skip), (5, 0));
+        let mut uncut = checkpoint();
+        uncut.advance(&page, 5);
+        assert_eq!(resumed, uncut);
+    }
+}
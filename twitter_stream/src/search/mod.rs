@@ -0,0 +1,149 @@
+pub mod archive;
+
+pub use archive::{ArchiveSearch, Checkpoint, MAX_ARCHIVE_RESULTS, SEARCH_ALL_PATH};
+
+use crate::{
+    pagination::{paginate, Page, Paginated},
+    stream_tweets::{RateLimitHeaders, StreamResponse, TweetsResponse},
+    TweetId, TwitterClient,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const SEARCH_RECENT_PATH: &str = "/2/tweets/search/recent";
+
+/// Maximum number of results per request of the recent search
+pub const MAX_RESULTS: usize = 100;
+const MIN_RESULTS: usize = 10;
+
+/// Query for the search endpoints, see:
+/// https://developer.twitter.com/en/docs/twitter-api/tweets/search/integrate/build-a-query
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub query: String,
+    /// Oldest time of the tweets (inclusive)
+    pub start_time: Option<DateTime<Utc>>,
+    /// Newest time of the tweets (exclusive)
+    pub end_time: Option<DateTime<Utc>>,
+    /// Returns tweets more recent than this id
+    pub since_id: Option<TweetId>,
+    /// Returns tweets older than this id
+    pub until_id: Option<TweetId>,
+    /// Results per request (between 10 and 100, or 500 for the full-archive search)
+    pub max_results: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn new<T: Into<String>>(query: T) -> Self {
+        Self {
+            query: query.into(),
+            start_time: None,
+            end_time: None,
+            since_id: None,
+            until_id: None,
+            max_results: None,
+        }
+    }
+
+    pub fn start_time(mut self, time: DateTime<Utc>) -> Self {
+        self.start_time = Some(time);
+        self
+    }
+
+    pub fn end_time(mut self, time: DateTime<Utc>) -> Self {
+        self.end_time = Some(time);
+        self
+    }
+
+    pub fn since_id(mut self, id: TweetId) -> Self {
+        self.since_id = Some(id);
+        self
+    }
+
+    pub fn until_id(mut self, id: TweetId) -> Self {
+        self.until_id = Some(id);
+        self
+    }
+
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Query parameters for the recent search (without the fields or the pagination token)
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        self.params(MAX_RESULTS)
+    }
+
+    fn params(&self, limit: usize) -> Vec<(&'static str, String)> {
+        let mut query = vec![("query", self.query.clone())];
+        if let Some(time) = self.start_time {
+            query.push(("start_time", format_time(time)));
+        }
+        if let Some(time) = self.end_time {
+            query.push(("end_time", format_time(time)));
+        }
+        if let Some(id) = self.since_id {
+            query.push(("since_id", id.to_string()));
+        }
+        if let Some(id) = self.until_id {
+            query.push(("until_id", id.to_string()));
+        }
+        if let Some(max_results) = self.max_results {
+            let max_results = max_results.clamp(MIN_RESULTS, limit);
+            query.push(("max_results", max_results.to_string()));
+        }
+        query
+    }
+}
+
+/// Time in the format expected by the API (eg: "2021-05-14T15:00:00Z")
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl TwitterClient {
+    /// Searches the tweets of the last 7 days, following the pagination
+    /// until there are no more results
+    pub fn search_recent(&self, query: SearchQuery) -> Paginated<StreamResponse> {
+        let client = self.clone();
+        paginate(move |next_token| {
+            let client = client.clone();
+            let query = query.clone();
+            async move {
+                let (_, res) = client
+                    .search_page(SEARCH_RECENT_PATH, &query, next_token.as_deref())
+                    .await?;
+                Ok(Page {
+                    next_token: res.meta.next_token.clone(),
+                    items: res.into_stream_responses(),
+                })
+            }
+        })
+    }
+
+    /// One page of results of a search endpoint (`path`)
+    pub async fn search_page(
+        &self,
+        path: &str,
+        query: &SearchQuery,
+        next_token: Option<&str>,
+    ) -> Result<(RateLimitHeaders, TweetsResponse)> {
+        let limit = match path {
+            SEARCH_ALL_PATH => MAX_ARCHIVE_RESULTS,
+            _ => MAX_RESULTS,
+        };
+        let mut params = query.params(limit);
+        params.extend(self.fields().to_query());
+        if let Some(token) = next_token {
+            params.push(("next_token", token.to_string()));
+        }
+        let res = self.get(path, &params).await?;
+        let rate_limit = RateLimitHeaders::from_headers(res.headers())?;
+        let res = res.text().await?;
+        let res = serde_json::from_str(&res)
+            .with_context(|| format!("Couldn't parse response:\n{}", res))?;
+        Ok((rate_limit, res))
+    }
+}
@@ -0,0 +1,162 @@
+//! Decoding of the snowflake ids used by twitter:
+//! https://developer.twitter.com/en/docs/twitter-ids
+//!
+//! An id is made of (from the most significant bits): 41 bits with the
+//! milliseconds since `TWITTER_EPOCH_MS`, 5 bits for the datacenter,
+//! 5 bits for the worker and 12 bits for the sequence number.
+//!
+//! Tweet and rule ids are always snowflakes, user ids only for the accounts
+//! created after 2013 (older ones are sequential numbers).
+use crate::{RuleId, TweetId, UserId};
+use chrono::{DateTime, TimeZone, Utc};
+use std::ops::RangeInclusive;
+
+/// Milliseconds since the unix epoch of the first snowflake (2010-11-04T01:42:54.657Z)
+pub const TWITTER_EPOCH_MS: i64 = 1_288_834_974_657;
+
+/// Last millisecond (since `TWITTER_EPOCH_MS`) that fits in the 41 bits of the
+/// timestamp (2080-07-10T17:30:30.208Z)
+pub const MAX_TIMESTAMP_MS: u64 = (1 << 41) - 1;
+
+const TIMESTAMP_SHIFT: u64 = 22;
+const DATACENTER_SHIFT: u64 = 17;
+const WORKER_SHIFT: u64 = 12;
+const DATACENTER_MASK: u64 = 0b1_1111;
+const WORKER_MASK: u64 = 0b1_1111;
+const SEQUENCE_MASK: u64 = 0b1111_1111_1111;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Snowflake {
+    pub timestamp: DateTime<Utc>,
+    pub datacenter: u8,
+    pub worker: u8,
+    pub sequence: u16,
+}
+
+impl Snowflake {
+    pub fn decode(id: u64) -> Self {
+        Self {
+            timestamp: timestamp(id),
+            datacenter: ((id >> DATACENTER_SHIFT) & DATACENTER_MASK) as u8,
+            worker: ((id >> WORKER_SHIFT) & WORKER_MASK) as u8,
+            sequence: (id & SEQUENCE_MASK) as u16,
+        }
+    }
+
+    pub fn encode(&self) -> u64 {
+        timestamp_bits(self.timestamp)
+            | (self.datacenter as u64 & DATACENTER_MASK) << DATACENTER_SHIFT
+            | (self.worker as u64 & WORKER_MASK) << WORKER_SHIFT
+            | (self.sequence as u64 & SEQUENCE_MASK)
+    }
+}
+
+/// Creation time of the id (millisecond precision)
+pub fn timestamp(id: u64) -> DateTime<Utc> {
+    let ms = (id >> TIMESTAMP_SHIFT) as i64 + TWITTER_EPOCH_MS;
+    Utc.timestamp_millis_opt(ms).unwrap()
+}
+
+/// Smallest id that can be created at `time`
+pub fn min_id(time: DateTime<Utc>) -> u64 {
+    timestamp_bits(time)
+}
+
+/// Largest id that can be created at `time`
+pub fn max_id(time: DateTime<Utc>) -> u64 {
+    timestamp_bits(time) | ((1 << TIMESTAMP_SHIFT) - 1)
+}
+
+/// Ids created between `start` and `end` (both included), eg: to build
+/// `since_id`/`until_id` queries or to bucket tweets by time
+pub fn id_range(start: DateTime<Utc>, end: DateTime<Utc>) -> RangeInclusive<u64> {
+    min_id(start)..=max_id(end)
+}
+
+/// Timestamp part of an id, times before `TWITTER_EPOCH_MS` map to 0 and
+/// times after `MAX_TIMESTAMP_MS` to the last timestamp
+fn timestamp_bits(time: DateTime<Utc>) -> u64 {
+    let ms = (time.timestamp_millis() - TWITTER_EPOCH_MS).max(0) as u64;
+    ms.min(MAX_TIMESTAMP_MS) << TIMESTAMP_SHIFT
+}
+
+macro_rules! snowflake_id {
+    ($name:ident) => {
+        impl $name {
+            pub fn snowflake(self) -> Snowflake {
+                Snowflake::decode(self.0)
+            }
+
+            pub fn timestamp(self) -> DateTime<Utc> {
+                timestamp(self.0)
+            }
+
+            /// Smallest id that can be created at `time`
+            pub fn min_at(time: DateTime<Utc>) -> Self {
+                Self(min_id(time))
+            }
+
+            /// Largest id that can be created at `time`
+            pub fn max_at(time: DateTime<Utc>) -> Self {
+                Self(max_id(time))
+            }
+        }
+    };
+}
+
+snowflake_id!(TweetId);
+snowflake_id!(UserId);
+snowflake_id!(RuleId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Id of the tweet used in the examples of the API docs
+    const TWEET_ID: u64 = 1212092628029698048;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn decodes_a_tweet_id() {
+        let snowflake = Snowflake::decode(TWEET_ID);
+        assert_eq!(snowflake.timestamp, time("2019-12-31T19:26:16.771Z"));
+        assert_eq!(snowflake.datacenter, 10);
+        assert_eq!(snowflake.worker, 7);
+        assert_eq!(snowflake.sequence, 0);
+        assert_eq!(snowflake.encode(), TWEET_ID);
+        assert_eq!(TweetId(TWEET_ID).timestamp(), snowflake.timestamp);
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let snowflake = Snowflake {
+            timestamp: time("2021-05-14T15:00:00.123Z"),
+            datacenter: 31,
+            worker: 1,
+            sequence: 4095,
+        };
+        assert_eq!(Snowflake::decode(snowflake.encode()), snowflake);
+    }
+
+    #[test]
+    fn ids_at_a_time() {
+        let at = time("2019-12-31T19:26:16.771Z");
+        assert_eq!(min_id(at), 1212092628028358656);
+        assert_eq!(max_id(at), 1212092628032552959);
+        assert!(id_range(at, at).contains(&TWEET_ID));
+        assert!(!id_range(at, at).contains(&(TWEET_ID - (1 << 22))));
+        assert_eq!(timestamp(min_id(at)), at);
+        assert_eq!(timestamp(max_id(at)), at);
+    }
+
+    #[test]
+    fn times_out_of_range() {
+        assert_eq!(min_id(time("2000-01-01T00:00:00Z")), 0);
+        let last = timestamp(max_id(time("2080-07-10T17:30:30.208Z")));
+        assert_eq!(max_id(time("3000-01-01T00:00:00Z")), max_id(last));
+        assert_eq!(max_id(last) >> 63, 0);
+    }
+}
@@ -0,0 +1,164 @@
+/// Splits the bytes received from the stream in lines (delimited by "\r\n").
+///
+/// The HTTP chunks don't follow the boundaries of the messages, so a chunk may
+/// contain part of a tweet or many of them. Incomplete lines are kept until the
+/// rest of the line arrives.
+#[derive(Debug, Default)]
+pub struct LineDecoder {
+    buffer: Vec<u8>,
+    /// Position up to which `buffer` has no delimiter
+    scanned: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Line {
+    /// Empty line, sent by twitter to keep the connection alive
+    Heartbeat,
+    Data(Vec<u8>),
+}
+
+const DELIMITER: &[u8] = b"\r\n";
+
+impl LineDecoder {
+    /// Adds a chunk of data and returns all the lines completed by it
+    pub fn decode(&mut self, chunk: &[u8]) -> Vec<Line> {
+        self.buffer.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(line) = self.next_line() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Bytes waiting for the rest of their line
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn next_line(&mut self) -> Option<Line> {
+        let pos = self.buffer[self.scanned..]
+            .windows(DELIMITER.len())
+            .position(|window| window == DELIMITER);
+        match pos {
+            Some(pos) => {
+                let end = self.scanned + pos;
+                let line = self.buffer[..end].to_vec();
+                self.buffer.drain(..end + DELIMITER.len());
+                self.scanned = 0;
+                if line.iter().all(|b| b.is_ascii_whitespace()) {
+                    Some(Line::Heartbeat)
+                } else {
+                    Some(Line::Data(line))
+                }
+            }
+            None => {
+                // the last byte may be the start of a delimiter
+                self.scanned = self.buffer.len().saturating_sub(DELIMITER.len() - 1);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        stream_tweets::{parse_line, StreamError, StreamResponse},
+        TwitterApiError,
+    };
+
+    const TWEET: &str = r#"{"data":{"author_id":"2244994945","conversation_id":"1390000000000000000","created_at":"2021-05-14T15:00:00.000Z","id":"1390000000000000000","public_metrics":{"like_count":1,"quote_count":0,"reply_count":0,"retweet_count":2},"text":"Hello\r\nworld"},"includes":{"users":[{"created_at":"2013-12-14T04:35:55.000Z","id":"2244994945","name":"Twitter Dev","username":"TwitterDev"}]},"matching_rules":[{"id":1390000000000000001,"tag":"test"}]}"#;
+
+    fn data(line: &str) -> Line {
+        Line::Data(line.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn splits_lines_in_one_chunk() {
+        let mut decoder = LineDecoder::default();
+        let lines = decoder.decode(b"{\"a\":1}\r\n{\"b\":2}\r\n");
+        assert_eq!(lines, vec![data("{\"a\":1}"), data("{\"b\":2}")]);
+        assert!(decoder.pending().is_empty());
+    }
+
+    #[test]
+    fn keeps_partial_lines() {
+        let mut decoder = LineDecoder::default();
+        assert!(decoder.decode(b"{\"a\":").is_empty());
+        assert!(decoder.decode(b"1}").is_empty());
+        assert_eq!(decoder.pending(), b"{\"a\":1}");
+        let lines = decoder.decode(b"\r\n{\"b\"");
+        assert_eq!(lines, vec![data("{\"a\":1}")]);
+        assert_eq!(decoder.pending(), b"{\"b\"");
+    }
+
+    #[test]
+    fn delimiter_split_between_chunks() {
+        let mut decoder = LineDecoder::default();
+        assert!(decoder.decode(b"{\"a\":1}\r").is_empty());
+        assert_eq!(decoder.decode(b"\n"), vec![data("{\"a\":1}")]);
+    }
+
+    #[test]
+    fn heartbeats() {
+        let mut decoder = LineDecoder::default();
+        let lines = decoder.decode(b"\r\n\r\n{\"a\":1}\r\n\r");
+        assert_eq!(
+            lines,
+            vec![Line::Heartbeat, Line::Heartbeat, data("{\"a\":1}")]
+        );
+        assert_eq!(decoder.decode(b"\n"), vec![Line::Heartbeat]);
+    }
+
+    #[test]
+    fn one_response_per_line() {
+        let payload = format!("{}\r\n\r\n{}\r\n", TWEET, TWEET).into_bytes();
+        // feed the payload in every possible pair of fragments
+        for split in 0..payload.len() {
+            let mut decoder = LineDecoder::default();
+            let mut lines = decoder.decode(&payload[..split]);
+            lines.extend(decoder.decode(&payload[split..]));
+            assert_eq!(lines.len(), 3);
+            let tweets = lines
+                .into_iter()
+                .filter_map(|line| parse_line::<StreamResponse>(line).ok())
+                .collect::<Vec<_>>();
+            assert_eq!(tweets.len(), 2);
+            assert!(tweets.iter().all(|o| o.data.text == "Hello\r\nworld"));
+        }
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let payload = format!("{}\r\n{}\r\n", TWEET, TWEET).into_bytes();
+        let mut decoder = LineDecoder::default();
+        let lines = payload
+            .iter()
+            .flat_map(|b| decoder.decode(&[*b]))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![data(TWEET), data(TWEET)]);
+    }
+
+    #[test]
+    fn malformed_tweet_is_a_parse_error() {
+        let line = data(r#"{"data":{"id":"1","text":5},"matching_rules":[]}"#);
+        match parse_line::<StreamResponse>(line) {
+            Err(StreamError::Parse(err)) => assert!(err.msg.contains(r#""text":5"#)),
+            res => panic!("Expected a parse error, got: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        let line = data(
+            r#"{"errors":[{"title":"operational-disconnect","disconnect_type":"OperationalDisconnect","detail":"This stream has been disconnected upstream for operational reasons.","type":"https://api.twitter.com/2/problems/operational-disconnect"}]}"#,
+        );
+        match parse_line::<StreamResponse>(line) {
+            Err(StreamError::Api(err)) => {
+                assert!(matches!(*err, TwitterApiError::OperationalDisconnect(_)))
+            }
+            res => panic!("Expected an api error, got: {:?}", res),
+        }
+    }
+}
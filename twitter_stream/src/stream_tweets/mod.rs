@@ -0,0 +1,293 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{future, stream, Stream, StreamExt};
+use reqwest::header::HeaderMap;
+use std::{
+    pin::Pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+use crate::{api_error::ErrorResponse, ApiConfig, TweetId, TwitterApiError, TwitterClient};
+
+pub mod decoder;
+pub mod raw;
+pub mod resilient;
+pub mod response;
+
+pub use decoder::{Line, LineDecoder};
+pub use raw::RawStreamResponse;
+pub use resilient::{Backoff, BackoffKind, ReconnectOptions, ResilientStream, StreamEvent};
+pub use response::*;
+
+pub const STREAM_PATH: &str = "/2/tweets/search/stream";
+pub const SAMPLE_STREAM_PATH: &str = "/2/tweets/sample/stream";
+
+/// Streaming endpoints of the API
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StreamEndpoint {
+    /// Tweets matching the rules of the stream
+    #[default]
+    Filtered,
+    /// Random sample of about 1% of all the tweets
+    Sample,
+}
+
+impl StreamEndpoint {
+    pub fn path(self) -> &'static str {
+        match self {
+            StreamEndpoint::Filtered => STREAM_PATH,
+            StreamEndpoint::Sample => SAMPLE_STREAM_PATH,
+        }
+    }
+}
+
+/// Stream of tweets returned by `TwitterClient::stream`
+pub type TweetStream<T = StreamResponse> =
+    Pin<Box<dyn Stream<Item = Result<T, StreamError>> + Send>>;
+
+/// Messages that can be read from the stream (one per line)
+pub trait StreamItem: Sized + Send + 'static {
+    fn from_line(line: &[u8]) -> Result<Self, StreamError>;
+    /// Id of the tweet, used to skip duplicates
+    fn tweet_id(&self) -> TweetId;
+}
+
+impl StreamItem for StreamResponse {
+    fn from_line(line: &[u8]) -> Result<Self, StreamError> {
+        serde_json::from_slice::<StreamResponse>(line).map_err(|err| line_error(line, err))
+    }
+
+    fn tweet_id(&self) -> TweetId {
+        self.data.id
+    }
+}
+
+/// Maximum number of minutes that can be recovered with `backfill_minutes`
+pub const MAX_BACKFILL_MINUTES: u8 = 5;
+
+/// `backfill_minutes` recovers the tweets sent while disconnected (up to 5 minutes),
+/// it requires Academic Research access.
+pub async fn stream_data(
+    bearer_token: &str,
+    config: &ApiConfig,
+    backfill_minutes: Option<u8>,
+) -> Result<(RateLimitHeaders, TweetStream)> {
+    TwitterClient::new(bearer_token, config.clone())?
+        .stream_backfill(backfill_minutes)
+        .await
+}
+
+/// Same as `stream_data` for the sampled stream (about 1% of all the tweets)
+pub async fn sample_stream(
+    bearer_token: &str,
+    config: &ApiConfig,
+    backfill_minutes: Option<u8>,
+) -> Result<(RateLimitHeaders, TweetStream)> {
+    TwitterClient::new(bearer_token, config.clone())?
+        .sample_stream(backfill_minutes)
+        .await
+}
+
+impl TwitterClient {
+    /// Connects to the filtered stream
+    pub async fn stream(&self) -> Result<(RateLimitHeaders, TweetStream)> {
+        self.stream_backfill(None).await
+    }
+
+    /// Connects to the filtered stream recovering the tweets of the last
+    /// `backfill_minutes` (Academic Research access only)
+    pub async fn stream_backfill(
+        &self,
+        backfill_minutes: Option<u8>,
+    ) -> Result<(RateLimitHeaders, TweetStream)> {
+        self.stream_as(backfill_minutes).await
+    }
+
+    /// Connects to the filtered stream reading the messages as `T`
+    /// (eg: `RawStreamResponse` to keep the original JSON)
+    pub async fn stream_as<T: StreamItem>(
+        &self,
+        backfill_minutes: Option<u8>,
+    ) -> Result<(RateLimitHeaders, TweetStream<T>)> {
+        self.connect_stream(StreamEndpoint::Filtered, backfill_minutes)
+            .await
+    }
+
+    /// Connects to the sampled stream (about 1% of all the tweets)
+    pub async fn sample_stream(
+        &self,
+        backfill_minutes: Option<u8>,
+    ) -> Result<(RateLimitHeaders, TweetStream)> {
+        self.connect_stream(StreamEndpoint::Sample, backfill_minutes)
+            .await
+    }
+
+    /// Connects to a streaming endpoint reading the messages as `T`
+    pub async fn connect_stream<T: StreamItem>(
+        &self,
+        endpoint: StreamEndpoint,
+        backfill_minutes: Option<u8>,
+    ) -> Result<(RateLimitHeaders, TweetStream<T>)> {
+        let mut query = self.fields().to_query();
+        if let Some(minutes) = backfill_minutes {
+            let minutes = minutes.min(MAX_BACKFILL_MINUTES);
+            query.push(("backfill_minutes", minutes.to_string()));
+        }
+        let res = self.get_stream(endpoint.path(), &query).await?;
+        let rate_limit = RateLimitHeaders::from_headers(res.headers())?;
+
+        let stream = watch_stalls(res.bytes_stream(), self.config().stall_timeout)
+            .scan(LineDecoder::default(), |decoder, chunk| {
+                let items = match chunk {
+                    Ok(chunk) => decoder.decode(&chunk).into_iter().map(parse_line).collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                future::ready(Some(stream::iter(items)))
+            })
+            .flatten();
+        Ok((rate_limit, Box::pin(stream)))
+    }
+}
+
+/// Reads the chunks of the response, if no data arrives in `stall_timeout` it
+/// yields `StreamError::Stalled` and ends the stream (twitter sends a keep-alive
+/// signal every 20 seconds).
+fn watch_stalls<S>(
+    chunks: S,
+    stall_timeout: Option<Duration>,
+) -> impl Stream<Item = Result<Bytes, StreamError>>
+where
+    S: Stream<Item = reqwest::Result<Bytes>>,
+{
+    let chunks = Box::pin(chunks);
+    stream::unfold(Some(chunks), move |chunks| async move {
+        let mut chunks = chunks?;
+        let chunk = match stall_timeout {
+            Some(duration) => match tokio::time::timeout(duration, chunks.next()).await {
+                Ok(chunk) => chunk,
+                Err(_) => return Some((Err(StreamError::Stalled(duration)), None)),
+            },
+            None => chunks.next().await,
+        };
+        chunk.map(|chunk| (chunk.map_err(StreamError::from), Some(chunks)))
+    })
+}
+
+pub(crate) fn parse_line<T: StreamItem>(line: Line) -> Result<T, StreamError> {
+    match line {
+        Line::Heartbeat => Err(StreamError::Heartbeat),
+        Line::Data(line) => T::from_line(&line),
+    }
+}
+
+/// Error for a line that couldn't be read, messages without tweet data
+/// may be errors sent by twitter (eg: operational-disconnect)
+pub(crate) fn line_error(line: &[u8], err: serde_json::Error) -> StreamError {
+    match serde_json::from_slice::<ErrorResponse>(line).map(|o| o.into_problem()) {
+        Ok(Some(problem)) => TwitterApiError::from_problem(problem).into(),
+        _ => StreamError::Parse(ParseError {
+            msg: String::from_utf8_lossy(line).into_owned(),
+            source: err,
+        }),
+    }
+}
+
+// "x-rate-limit-limit": "50",
+// "x-rate-limit-reset": "1621007751",
+// "x-rate-limit-remaining": "26",
+#[derive(Clone, Debug)]
+pub struct RateLimitHeaders {
+    pub limit: Option<usize>,
+    pub reset: Option<Duration>,
+    pub remaining: Option<usize>,
+}
+
+impl RateLimitHeaders {
+    pub fn from_headers(header_map: &HeaderMap) -> Result<Self> {
+        let limit = match header_map.get("x-rate-limit-limit") {
+            Some(o) => Some(o.to_str()?.parse()?),
+            None => None,
+        };
+        let reset = match header_map.get("x-rate-limit-reset") {
+            Some(o) => Some(Duration::from_secs(o.to_str()?.parse()?)),
+            None => None,
+        };
+        let remaining = match header_map.get("x-rate-limit-remaining") {
+            Some(o) => Some(o.to_str()?.parse()?),
+            None => None,
+        };
+        Ok(RateLimitHeaders {
+            limit,
+            reset,
+            remaining,
+        })
+    }
+
+    // Get time until rate limit reset
+    pub fn duration_until_reset(&self) -> Option<Duration> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH);
+        if let (Some(0), Some(reset), Ok(now)) = (self.remaining, self.reset, now) {
+            reset.checked_sub(now)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error("Received a keep-alive signal")]
+    Heartbeat,
+    #[error("Error reading chunk of stream")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("{0}")]
+    Parse(ParseError),
+    #[error("{0}")]
+    Api(Box<TwitterApiError>),
+    #[error("No data received from the stream in {0:?}")]
+    Stalled(Duration),
+    #[error("Maximum number of connection resets ({0}) reached")]
+    MaxResets(usize),
+}
+
+impl From<TwitterApiError> for StreamError {
+    fn from(err: TwitterApiError) -> Self {
+        StreamError::Api(Box::new(err))
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Error parsing tweet data:\n{source}\n{msg}")]
+pub struct ParseError {
+    pub msg: String,
+    pub source: serde_json::Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_stream() {
+        let timeout = Duration::from_secs(30);
+        let chunks = stream::iter(vec![Ok(Bytes::from("\r\n"))]).chain(stream::pending());
+        let mut stream = Box::pin(watch_stalls(chunks, Some(timeout)));
+        assert_eq!(stream.next().await.unwrap().unwrap(), Bytes::from("\r\n"));
+        let start = tokio::time::Instant::now();
+        match stream.next().await {
+            Some(Err(StreamError::Stalled(duration))) => assert_eq!(duration, timeout),
+            res => panic!("Expected a stalled stream, got: {:?}", res),
+        }
+        assert_eq!(start.elapsed(), timeout);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_waiting_without_timeout() {
+        let chunks = stream::pending::<reqwest::Result<Bytes>>();
+        let mut stream = Box::pin(watch_stalls(chunks, None));
+        let next = tokio::time::timeout(Duration::from_secs(3600), stream.next()).await;
+        assert!(next.is_err());
+    }
+}
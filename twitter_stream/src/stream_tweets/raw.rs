@@ -0,0 +1,94 @@
+use super::{line_error, StreamError, StreamItem, StreamResponse};
+use crate::ids::TweetId;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+/// Message of the stream as it was sent by twitter, it serializes to the
+/// original JSON so no field is lost when archiving the data.
+///
+/// The typed view (`StreamResponse`) is parsed the first time it's requested.
+#[derive(Clone, Debug)]
+pub struct RawStreamResponse {
+    id: TweetId,
+    raw: Box<RawValue>,
+    typed: OnceCell<StreamResponse>,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    data: Option<EnvelopeData>,
+}
+
+#[derive(Deserialize)]
+struct EnvelopeData {
+    id: TweetId,
+}
+
+impl RawStreamResponse {
+    pub fn from_raw(raw: Box<RawValue>) -> serde_json::Result<Self> {
+        let envelope = serde_json::from_str::<Envelope>(raw.get())?;
+        let id = envelope
+            .data
+            .map(|o| o.id)
+            .ok_or_else(|| serde::de::Error::missing_field("data"))?;
+        Ok(Self {
+            id,
+            raw,
+            typed: OnceCell::new(),
+        })
+    }
+
+    pub fn id(&self) -> TweetId {
+        self.id
+    }
+
+    /// Original JSON text
+    pub fn json(&self) -> &str {
+        self.raw.get()
+    }
+
+    pub fn to_value(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_str(self.raw.get())
+    }
+
+    pub fn typed(&self) -> serde_json::Result<&StreamResponse> {
+        self.typed
+            .get_or_try_init(|| serde_json::from_str(self.raw.get()))
+    }
+
+    pub fn into_raw(self) -> Box<RawValue> {
+        self.raw
+    }
+}
+
+impl StreamItem for RawStreamResponse {
+    fn from_line(line: &[u8]) -> Result<Self, StreamError> {
+        serde_json::from_slice::<Box<RawValue>>(line)
+            .and_then(Self::from_raw)
+            .map_err(|err| line_error(line, err))
+    }
+
+    fn tweet_id(&self) -> TweetId {
+        self.id
+    }
+}
+
+impl Serialize for RawStreamResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawStreamResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw: Box<RawValue> = Deserialize::deserialize(deserializer)?;
+        Self::from_raw(raw).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,405 @@
+use super::{
+    RateLimitHeaders, RawStreamResponse, StreamEndpoint, StreamError, StreamItem, StreamResponse,
+    TweetStream, MAX_BACKFILL_MINUTES,
+};
+use crate::{TweetId, TwitterApiError, TwitterClient};
+use futures::{
+    stream,
+    task::{Context, Poll},
+    Stream, StreamExt,
+};
+use std::{
+    cmp::max,
+    collections::{HashSet, VecDeque},
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+/// Stream of tweets that reconnects when the connection drops, following
+/// the backoff policy recommended by twitter:
+/// https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/integrate/handling-disconnections
+///
+/// Yields `Err` only when it can't continue (eg: the maximum number of resets
+/// was reached or the credentials are invalid), after that the stream ends.
+pub struct ResilientStream<T = StreamResponse> {
+    inner: Pin<Box<dyn Stream<Item = Result<StreamEvent<T>, StreamError>> + Send>>,
+}
+
+#[derive(Debug)]
+pub enum StreamEvent<T = StreamResponse> {
+    Tweet(Box<T>),
+    /// An error that doesn't stop the stream (eg: a message that couldn't be parsed)
+    Error(StreamError),
+    /// A connection was established
+    Connected {
+        rate_limit: RateLimitHeaders,
+        backfill_minutes: Option<u8>,
+    },
+    /// The connection was lost or couldn't be established, a new attempt
+    /// will be made after `wait`
+    Reconnecting {
+        attempt: usize,
+        wait: Duration,
+        kind: BackoffKind,
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReconnectOptions {
+    /// Maximum number of connection resets, unlimited if `None`
+    pub max_resets: Option<usize>,
+    /// Recovers the tweets sent while disconnected (up to 5 minutes) using
+    /// `backfill_minutes`, requires Academic Research access.
+    /// Tweets already received are skipped as twitter may send them again.
+    pub backfill: bool,
+    /// Endpoint to connect to, the filtered stream by default
+    pub endpoint: StreamEndpoint,
+}
+
+impl ResilientStream {
+    pub fn new(client: TwitterClient, options: ReconnectOptions) -> Self {
+        Self::start(client, options)
+    }
+}
+
+impl ResilientStream<RawStreamResponse> {
+    /// Yields the messages with their original JSON
+    pub fn raw(client: TwitterClient, options: ReconnectOptions) -> Self {
+        Self::start(client, options)
+    }
+}
+
+impl<T: StreamItem> ResilientStream<T> {
+    fn start(client: TwitterClient, options: ReconnectOptions) -> Self {
+        let state = State {
+            client,
+            max_resets: options.max_resets,
+            backfill: options.backfill,
+            endpoint: options.endpoint,
+            disconnected_at: None,
+            seen: RecentIds::default(),
+            stream: None,
+            rate_limit: None,
+            backoff: Backoff::default(),
+            resets: 0,
+            wait: None,
+            finished: false,
+        };
+        let inner = stream::unfold(state, |mut state| async move {
+            state.next_event().await.map(|event| (event, state))
+        });
+        Self {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+impl<T> Stream for ResilientStream<T> {
+    type Item = Result<StreamEvent<T>, StreamError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+struct State<T> {
+    client: TwitterClient,
+    max_resets: Option<usize>,
+    backfill: bool,
+    endpoint: StreamEndpoint,
+    /// Time of the first failure since the last successful connection
+    disconnected_at: Option<Instant>,
+    /// Ids of the last tweets received, to skip duplicates when using backfill
+    seen: RecentIds,
+    stream: Option<TweetStream<T>>,
+    /// Rate limit of the last connection
+    rate_limit: Option<RateLimitHeaders>,
+    backoff: Backoff,
+    resets: usize,
+    /// Time to wait before the next connection attempt
+    wait: Option<Duration>,
+    finished: bool,
+}
+
+impl<T: StreamItem> State<T> {
+    async fn next_event(&mut self) -> Option<Result<StreamEvent<T>, StreamError>> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => return Some(self.connect().await),
+            };
+
+            match stream.next().await {
+                Some(Ok(tweet)) => {
+                    if self.backfill && !self.seen.insert(tweet.tweet_id()) {
+                        continue;
+                    }
+                    return Some(Ok(StreamEvent::Tweet(Box::new(tweet))));
+                }
+                Some(Err(StreamError::Heartbeat)) => {}
+                Some(Err(err @ StreamError::Reqwest(_)))
+                | Some(Err(err @ StreamError::Stalled(_))) => {
+                    return Some(self.disconnected(BackoffKind::Network, err.to_string()));
+                }
+                Some(Err(StreamError::Api(err))) if !matches!(*err, TwitterApiError::Other(_)) => {
+                    return Some(self.api_error(*err));
+                }
+                Some(Err(err)) => return Some(Ok(StreamEvent::Error(err))),
+                None => {
+                    let reason = "Connection closed by the server".to_string();
+                    return Some(self.disconnected(BackoffKind::Network, reason));
+                }
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<StreamEvent<T>, StreamError> {
+        if let Some(wait) = self.wait.take() {
+            tokio::time::sleep(wait).await;
+        }
+
+        let backfill_minutes = match self.disconnected_at {
+            Some(time) if self.backfill => {
+                let minutes = (time.elapsed().as_secs() / 60 + 1).min(MAX_BACKFILL_MINUTES as u64);
+                Some(minutes as u8)
+            }
+            _ => None,
+        };
+
+        match self
+            .client
+            .connect_stream(self.endpoint, backfill_minutes)
+            .await
+        {
+            Ok((rate_limit, stream)) => {
+                self.stream = Some(stream);
+                self.rate_limit = Some(rate_limit.clone());
+                self.backoff.reset();
+                self.disconnected_at = None;
+                Ok(StreamEvent::Connected {
+                    rate_limit,
+                    backfill_minutes,
+                })
+            }
+            Err(err) => match err.downcast::<TwitterApiError>() {
+                Ok(err) => self.api_error(err),
+                Err(err) => {
+                    let kind = match err.downcast_ref::<reqwest::Error>() {
+                        Some(err) if err.status().is_some() => BackoffKind::Http,
+                        Some(_) => BackoffKind::Network,
+                        None => BackoffKind::Http,
+                    };
+                    self.disconnected(kind, format!("{:#}", err))
+                }
+            },
+        }
+    }
+
+    /// Reconnects or stops the stream depending on the error
+    fn api_error(&mut self, err: TwitterApiError) -> Result<StreamEvent<T>, StreamError> {
+        let kind = match &err {
+            TwitterApiError::OperationalDisconnect(_) => BackoffKind::Network,
+            TwitterApiError::ConnectionException(_) | TwitterApiError::Other(_) => {
+                BackoffKind::Http
+            }
+            TwitterApiError::RateLimited(_, rate_limit) => {
+                self.rate_limit = Some(rate_limit.clone());
+                BackoffKind::RateLimit
+            }
+            // waiting won't help
+            TwitterApiError::UsageCapExceeded(_) | TwitterApiError::Unauthorized(_) => {
+                self.stream = None;
+                self.finished = true;
+                return Err(err.into());
+            }
+        };
+        self.disconnected(kind, err.to_string())
+    }
+
+    /// Schedules the next connection attempt
+    fn disconnected(
+        &mut self,
+        kind: BackoffKind,
+        reason: String,
+    ) -> Result<StreamEvent<T>, StreamError> {
+        self.stream = None;
+        if self.disconnected_at.is_none() {
+            self.disconnected_at = Some(Instant::now());
+        }
+
+        if let Some(max_resets) = self.max_resets {
+            if self.resets >= max_resets {
+                self.finished = true;
+                return Err(StreamError::MaxResets(max_resets));
+            }
+        }
+        self.resets += 1;
+
+        let mut wait = self.backoff.next(kind);
+        if let Some(reset) = self
+            .rate_limit
+            .as_ref()
+            .and_then(|o| o.duration_until_reset())
+        {
+            wait = max(wait, reset);
+        }
+        self.wait = Some(wait);
+
+        Ok(StreamEvent::Reconnecting {
+            attempt: self.resets,
+            wait,
+            kind,
+            reason,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackoffKind {
+    /// TCP/IP level errors, backs off linearly by 250ms up to 16 seconds
+    Network,
+    /// HTTP errors, backs off exponentially starting at 5 seconds up to 320 seconds
+    Http,
+    /// HTTP 429 errors, backs off exponentially starting at 1 minute
+    RateLimit,
+}
+
+impl BackoffKind {
+    /// Time to wait for the given attempt (starting at 1)
+    pub fn delay(self, attempt: u32) -> Duration {
+        let attempt = attempt.max(1);
+        match self {
+            BackoffKind::Network => {
+                Duration::from_millis(250 * attempt as u64).min(Duration::from_secs(16))
+            }
+            BackoffKind::Http => {
+                let exp = 2u64.pow((attempt - 1).min(6));
+                Duration::from_secs(5 * exp)
+            }
+            BackoffKind::RateLimit => {
+                let exp = 2u64.pow((attempt - 1).min(10));
+                Duration::from_secs(60 * exp)
+            }
+        }
+    }
+}
+
+/// Keeps the count of consecutive attempts for the same kind of error
+#[derive(Debug, Default)]
+pub struct Backoff {
+    kind: Option<BackoffKind>,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn next(&mut self, kind: BackoffKind) -> Duration {
+        if self.kind != Some(kind) {
+            self.kind = Some(kind);
+            self.attempts = 0;
+        }
+        self.attempts += 1;
+        kind.delay(self.attempts)
+    }
+
+    pub fn reset(&mut self) {
+        self.kind = None;
+        self.attempts = 0;
+    }
+}
+
+/// Set of the last `capacity` ids inserted
+struct RecentIds {
+    ids: HashSet<TweetId>,
+    order: VecDeque<TweetId>,
+    capacity: usize,
+}
+
+impl Default for RecentIds {
+    fn default() -> Self {
+        Self {
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+            capacity: 50_000,
+        }
+    }
+}
+
+impl RecentIds {
+    /// Returns false if the id was already present
+    fn insert(&mut self, id: TweetId) -> bool {
+        if self.ids.contains(&id) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+        self.ids.insert(id);
+        self.order.push_back(id);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(kind: BackoffKind, attempts: u32) -> Vec<u64> {
+        (1..=attempts)
+            .map(|attempt| kind.delay(attempt).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn network_backoff() {
+        assert_eq!(delays(BackoffKind::Network, 4), vec![250, 500, 750, 1000]);
+        assert_eq!(BackoffKind::Network.delay(64), Duration::from_secs(16));
+        assert_eq!(BackoffKind::Network.delay(1000), Duration::from_secs(16));
+    }
+
+    #[test]
+    fn http_backoff() {
+        assert_eq!(
+            delays(BackoffKind::Http, 8),
+            vec![5, 10, 20, 40, 80, 160, 320, 320]
+                .into_iter()
+                .map(|s| s * 1000)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rate_limit_backoff() {
+        assert_eq!(
+            delays(BackoffKind::RateLimit, 4),
+            vec![60_000, 120_000, 240_000, 480_000]
+        );
+        // attempt 0 is the same as the first attempt
+        assert_eq!(BackoffKind::RateLimit.delay(0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn counts_consecutive_attempts_of_the_same_kind() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.next(BackoffKind::Http), Duration::from_secs(5));
+        assert_eq!(backoff.next(BackoffKind::Http), Duration::from_secs(10));
+        // another kind starts over
+        assert_eq!(
+            backoff.next(BackoffKind::Network),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            backoff.next(BackoffKind::Network),
+            Duration::from_millis(500)
+        );
+        assert_eq!(backoff.next(BackoffKind::Http), Duration::from_secs(5));
+        backoff.reset();
+        assert_eq!(backoff.next(BackoffKind::Http), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,426 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_error::Problem,
+    ids::{RuleId, TweetId, UserId},
+    tweetid2url,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamResponse {
+    pub data: StreamResponseData,
+    #[serde(default)]
+    pub includes: StreamResponseIncludes,
+    pub matching_rules: Option<Vec<RuleMatch>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamResponseData {
+    pub id: TweetId,
+    pub author_id: Option<UserId>,
+    pub url: String,
+    pub text: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub conversation_id: Option<TweetId>,
+    #[serde(default)]
+    pub referenced_tweets: Option<Vec<ReferencedTweets>>,
+    pub public_metrics: Option<PublicMetrics>,
+    pub entities: Option<Entities>,
+    pub lang: Option<String>,
+    pub source: Option<String>,
+    pub possibly_sensitive: Option<bool>,
+    pub reply_settings: Option<String>,
+    pub in_reply_to_user_id: Option<UserId>,
+    pub geo: Option<TweetGeo>,
+    pub attachments: Option<Attachments>,
+    pub context_annotations: Option<Vec<ContextAnnotation>>,
+    pub withheld: Option<Withheld>,
+}
+
+impl From<StreamResponseDataRaw> for StreamResponseData {
+    fn from(raw: StreamResponseDataRaw) -> Self {
+        let url = tweetid2url(raw.id);
+        Self {
+            id: raw.id,
+            author_id: raw.author_id,
+            url,
+            text: raw.text,
+            created_at: raw.created_at,
+            conversation_id: raw.conversation_id,
+            referenced_tweets: raw.referenced_tweets,
+            public_metrics: raw.public_metrics,
+            entities: raw.entities,
+            lang: raw.lang,
+            source: raw.source,
+            possibly_sensitive: raw.possibly_sensitive,
+            reply_settings: raw.reply_settings,
+            in_reply_to_user_id: raw.in_reply_to_user_id,
+            geo: raw.geo,
+            attachments: raw.attachments,
+            context_annotations: raw.context_annotations,
+            withheld: raw.withheld,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StreamResponseData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let response: StreamResponseDataRaw = Deserialize::deserialize(deserializer)?;
+        Ok(response.into())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamResponseDataRaw {
+    pub id: TweetId,
+    pub author_id: Option<UserId>,
+    pub url: Option<String>,
+    pub text: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub conversation_id: Option<TweetId>,
+    #[serde(default)]
+    pub referenced_tweets: Option<Vec<ReferencedTweets>>,
+    pub public_metrics: Option<PublicMetrics>,
+    pub entities: Option<Entities>,
+    pub lang: Option<String>,
+    pub source: Option<String>,
+    pub possibly_sensitive: Option<bool>,
+    pub reply_settings: Option<String>,
+    pub in_reply_to_user_id: Option<UserId>,
+    pub geo: Option<TweetGeo>,
+    pub attachments: Option<Attachments>,
+    pub context_annotations: Option<Vec<ContextAnnotation>>,
+    pub withheld: Option<Withheld>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StreamResponseIncludes {
+    #[serde(default)]
+    pub users: Vec<UserData>,
+    #[serde(default)]
+    pub tweets: Vec<StreamResponseData>,
+    #[serde(default)]
+    pub media: Vec<Media>,
+    #[serde(default)]
+    pub places: Vec<Place>,
+    #[serde(default)]
+    pub polls: Vec<Poll>,
+}
+
+impl StreamResponseIncludes {
+    /// Objects of the includes referenced by `tweet`, the author is the first user
+    pub fn for_tweet(&self, tweet: &StreamResponseData) -> Self {
+        let mut users = Vec::new();
+        let mut add_user = |user: &UserData| {
+            if !users.iter().any(|o: &UserData| o.id == user.id) {
+                users.push(user.clone());
+            }
+        };
+        let user_ids = tweet.author_id.iter().chain(&tweet.in_reply_to_user_id);
+        for id in user_ids {
+            self.users
+                .iter()
+                .filter(|o| &o.id == id)
+                .for_each(&mut add_user);
+        }
+        let mentions = tweet.entities.as_ref().and_then(|o| o.mentions.as_ref());
+        for mention in mentions.into_iter().flatten() {
+            self.users
+                .iter()
+                .filter(|o| o.username == mention.username)
+                .for_each(&mut add_user);
+        }
+
+        let tweets = self
+            .tweets
+            .iter()
+            .filter(|o| {
+                tweet
+                    .referenced_tweets
+                    .iter()
+                    .flatten()
+                    .any(|reference| reference.id == o.id)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for author_id in tweets.iter().filter_map(|o| o.author_id.as_ref()) {
+            self.users
+                .iter()
+                .filter(|o| &o.id == author_id)
+                .for_each(&mut add_user);
+        }
+
+        let attachments = tweet.attachments.as_ref();
+        let media_keys = attachments.and_then(|o| o.media_keys.as_ref());
+        let poll_ids = attachments.and_then(|o| o.poll_ids.as_ref());
+        let place_id = tweet.geo.as_ref().and_then(|o| o.place_id.as_ref());
+
+        Self {
+            users,
+            tweets,
+            media: self
+                .media
+                .iter()
+                .filter(|o| {
+                    media_keys
+                        .into_iter()
+                        .flatten()
+                        .any(|key| key == &o.media_key)
+                })
+                .cloned()
+                .collect(),
+            places: self
+                .places
+                .iter()
+                .filter(|o| place_id == Some(&o.id))
+                .cloned()
+                .collect(),
+            polls: self
+                .polls
+                .iter()
+                .filter(|o| poll_ids.into_iter().flatten().any(|id| id == &o.id))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Response of the endpoints that return a list of tweets (eg: search)
+#[derive(Clone, Debug, Deserialize)]
+pub struct TweetsResponse {
+    #[serde(default)]
+    pub data: Vec<StreamResponseData>,
+    #[serde(default)]
+    pub includes: StreamResponseIncludes,
+    #[serde(default)]
+    pub meta: ResponseMeta,
+    /// Partial errors (eg: a referenced tweet that was deleted)
+    #[serde(default)]
+    pub errors: Vec<Problem>,
+}
+
+impl TweetsResponse {
+    /// Splits the response in one `StreamResponse` per tweet, each one with
+    /// its own includes (like the messages of the stream)
+    pub fn into_stream_responses(self) -> Vec<StreamResponse> {
+        let includes = self.includes;
+        self.data
+            .into_iter()
+            .map(|data| StreamResponse {
+                includes: includes.for_tweet(&data),
+                data,
+                matching_rules: None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ResponseMeta {
+    pub result_count: Option<usize>,
+    pub newest_id: Option<TweetId>,
+    pub oldest_id: Option<TweetId>,
+    pub next_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserData {
+    pub id: UserId,
+    pub name: String,
+    pub username: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub pinned_tweet_id: Option<TweetId>,
+    pub profile_image_url: Option<String>,
+    pub protected: Option<bool>,
+    pub public_metrics: Option<UserPublicMetrics>,
+    pub url: Option<String>,
+    pub verified: Option<bool>,
+    pub withheld: Option<Withheld>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserPublicMetrics {
+    pub followers_count: usize,
+    pub following_count: usize,
+    pub tweet_count: usize,
+    pub listed_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub id: RuleId,
+    pub tag: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferencedTweets {
+    pub id: TweetId,
+    #[serde(rename = "type")]
+    pub reference_type: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublicMetrics {
+    pub retweet_count: usize,
+    pub reply_count: usize,
+    pub like_count: usize,
+    pub quote_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entities {
+    pub annotations: Option<Vec<EntityAnnotation>>,
+    pub urls: Option<Vec<EntityUrl>>,
+    pub hashtags: Option<Vec<EntityTag>>,
+    pub mentions: Option<Vec<EntityMention>>,
+    pub cashtags: Option<Vec<EntityTag>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityAnnotation {
+    pub start: usize,
+    pub end: usize,
+    pub probability: f32,
+    #[serde(rename = "type")]
+    pub annotation_type: String,
+    pub normalized_text: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityUrl {
+    pub start: usize,
+    pub end: usize,
+    pub url: String,
+    pub expanded_url: String,
+    pub display_url: String,
+    pub unwound_url: Option<String>,
+    pub images: Option<Vec<UrlImage>>,
+    pub status: Option<usize>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UrlImage {
+    pub url: String,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityTag {
+    pub start: usize,
+    pub end: usize,
+    pub tag: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityMention {
+    pub start: usize,
+    pub end: usize,
+    pub username: String,
+    pub id: Option<UserId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TweetGeo {
+    pub place_id: Option<String>,
+    pub coordinates: Option<Coordinates>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Coordinates {
+    #[serde(rename = "type")]
+    pub coordinates_type: String,
+    /// Longitude and latitude
+    pub coordinates: Vec<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Attachments {
+    pub media_keys: Option<Vec<String>>,
+    pub poll_ids: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContextAnnotation {
+    pub domain: ContextEntity,
+    pub entity: ContextEntity,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContextEntity {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Withheld {
+    pub copyright: Option<bool>,
+    #[serde(default)]
+    pub country_codes: Vec<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Media {
+    pub media_key: String,
+    #[serde(rename = "type")]
+    pub media_type: String,
+    pub url: Option<String>,
+    pub preview_image_url: Option<String>,
+    pub duration_ms: Option<usize>,
+    pub height: Option<usize>,
+    pub width: Option<usize>,
+    pub alt_text: Option<String>,
+    pub public_metrics: Option<MediaPublicMetrics>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MediaPublicMetrics {
+    pub view_count: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Place {
+    pub id: String,
+    pub full_name: String,
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub place_type: Option<String>,
+    pub contained_within: Option<Vec<String>>,
+    pub geo: Option<PlaceGeo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaceGeo {
+    #[serde(rename = "type")]
+    pub geo_type: String,
+    /// Bounding box: west longitude, south latitude, east longitude, north latitude
+    pub bbox: Vec<f64>,
+    pub properties: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Poll {
+    pub id: String,
+    pub options: Vec<PollOption>,
+    pub duration_minutes: Option<usize>,
+    pub end_datetime: Option<DateTime<Utc>>,
+    pub voting_status: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PollOption {
+    pub position: usize,
+    pub label: String,
+    pub votes: usize,
+}
@@ -0,0 +1,77 @@
+use crate::{
+    lookup::UsersResponse,
+    pagination::{paginate, Page, Paginated},
+    stream_tweets::{StreamResponse, TweetsResponse, UserData},
+    TwitterClient, UserId,
+};
+use serde::de::DeserializeOwned;
+
+/// Maximum number of results per request of the timelines
+pub const MAX_TIMELINE_RESULTS: usize = 100;
+/// Maximum number of results per request of the followers and following lists
+pub const MAX_FOLLOWS_RESULTS: usize = 1000;
+
+impl TwitterClient {
+    /// Tweets posted by the user, from the most recent
+    pub fn user_tweets(&self, id: UserId) -> Paginated<StreamResponse> {
+        self.timeline(format!("/2/users/{}/tweets", id))
+    }
+
+    /// Tweets mentioning the user, from the most recent
+    pub fn user_mentions(&self, id: UserId) -> Paginated<StreamResponse> {
+        self.timeline(format!("/2/users/{}/mentions", id))
+    }
+
+    /// Users following the user
+    pub fn followers(&self, id: UserId) -> Paginated<UserData> {
+        self.follows(format!("/2/users/{}/followers", id))
+    }
+
+    /// Users followed by the user
+    pub fn following(&self, id: UserId) -> Paginated<UserData> {
+        self.follows(format!("/2/users/{}/following", id))
+    }
+
+    fn timeline(&self, path: String) -> Paginated<StreamResponse> {
+        let mut query = self.fields().to_query();
+        query.push(("max_results", MAX_TIMELINE_RESULTS.to_string()));
+        self.paginated(path, query, |res: TweetsResponse| {
+            (res.meta.next_token.clone(), res.into_stream_responses())
+        })
+    }
+
+    fn follows(&self, path: String) -> Paginated<UserData> {
+        let mut query = self.user_fields_query();
+        query.push(("max_results", MAX_FOLLOWS_RESULTS.to_string()));
+        self.paginated(path, query, |res: UsersResponse| {
+            (res.meta.next_token, res.data)
+        })
+    }
+
+    /// Follows the `pagination_token` of the endpoints of users
+    fn paginated<R, T, F>(
+        &self,
+        path: String,
+        query: Vec<(&'static str, String)>,
+        split: F,
+    ) -> Paginated<T>
+    where
+        R: DeserializeOwned,
+        T: Send + 'static,
+        F: Fn(R) -> (Option<String>, Vec<T>) + Copy + Send + Sync + 'static,
+    {
+        let client = self.clone();
+        paginate(move |token| {
+            let client = client.clone();
+            let path = path.clone();
+            let mut query = query.clone();
+            async move {
+                if let Some(token) = token {
+                    query.push(("pagination_token", token));
+                }
+                let (next_token, items) = split(client.get_json::<R, _>(&path, &query).await?);
+                Ok(Page { items, next_token })
+            }
+        })
+    }
+}